@@ -0,0 +1,26 @@
+use rs3a::lint::{has_errors, lint_file};
+use std::env;
+use std::process::exit;
+
+// Usage: cargo run --example lint -- FILE...
+// Exits with a non-zero status if any file has errors.
+fn main() {
+    let mut failed = false;
+    for path in env::args().skip(1) {
+        match lint_file(&path) {
+            Ok(diagnostics) => {
+                for diagnostic in &diagnostics {
+                    println!("{}: {}", path, diagnostic);
+                }
+                failed |= has_errors(&diagnostics);
+            }
+            Err(err) => {
+                println!("{}: {}", path, err);
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        exit(1);
+    }
+}
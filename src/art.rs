@@ -8,21 +8,39 @@ use std::fs::File;
 use std::io::{self, BufRead, BufReader, Cursor, Read};
 use std::path::Path;
 use std::str::FromStr;
+use std::time::Duration;
 
+use crate::asciicast::{self, AsciicastOptions, CastEvent, CastHeader};
+use crate::bitmap::BitmapFont;
 use crate::chars::{Char, UNDERSCORE};
 use crate::colors::apply_sgr;
 use crate::content::Cell;
+use crate::delay::{clamp_delay, delays_from_timestamps};
 use crate::error::{Error, Result};
 use crate::font::Font;
-use crate::helpers::json_quote;
-use crate::ttyrec::{TtyrecFrame, TtyrecReader};
+use crate::gif;
+use crate::header::{LegacyColorMode, LegacyHeaderInfo};
+use crate::helpers::{escape_html, fill_template, in_range, json_quote, Fmt};
+use crate::json::{self, JsonValue};
+use crate::lines::{Fix, Lines, ParseOptions};
+use crate::png;
+use crate::raster::{render_frame, Canvas};
+use crate::svg::SvgOptions;
+use crate::term::Terminal;
+use crate::timeline::Timeline;
+use crate::ttyrec::{TtyrecFrame, TtyrecOptions, TtyrecReader};
 use crate::{chars::normalize_text, content::Frames, header::Header};
 use crate::{content::Frame, delay::Delay, header::ExtraHeaderKey, ColorPair, Comments, Palette};
 use crate::{CSSColorMap, Color, Color4};
 
+/// JSON Schema of the document written by [`Art::to_json`] and read by
+/// [`Art::from_json`].
+pub const JSON_SCHEMA: &str = include_str!("../schema/3a.schema.json");
+
 /// Represents a complete 3a ASCII art animation, including header, frames,
 /// attached content, and extra blocks.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Art {
     pub(crate) header: Header,
     pub(crate) frames: Frames,
@@ -92,34 +110,156 @@ impl Art {
 
     /// Remove all frames out of inclusive subrange
     pub fn slice(&mut self, from: usize, to: usize) {
+        self.map_delays(|delays| {
+            let to = in_range(to, 0, delays.len());
+            let from = from.min(to);
+            delays.truncate(to + 1);
+            delays.drain(..from);
+        });
         self.frames.slice(from, to);
     }
 
     /// Swap two frames
     pub fn swap(&mut self, a: usize, b: usize) {
+        self.map_delays(|delays| {
+            if a < delays.len() && b < delays.len() {
+                delays.swap(a, b);
+            }
+        });
         self.frames.swap(a, b);
     }
 
     /// Reverse frames
     pub fn reverse(&mut self) {
+        self.map_delays(|delays| delays.reverse());
         self.frames.reverse();
     }
 
-    /// Deduplicate frames
+    /// Deduplicate frames.
+    /// Delays of merged frames are summed, so the timing stays the same.
     pub fn dedup(&mut self) {
+        let frames = &self.frames.frames;
+        let repeated: Vec<bool> = (0..frames.len())
+            .map(|f| f > 0 && frames[f] == frames[f - 1])
+            .collect();
+        self.map_delays(|delays| {
+            let mut merged: Vec<usize> = Vec::with_capacity(delays.len());
+            for (delay, repeated) in delays.iter().zip(repeated) {
+                match merged.last_mut() {
+                    Some(last) if repeated => *last += delay,
+                    _ => merged.push(*delay),
+                }
+            }
+            *delays = merged;
+        });
         self.frames.dedup();
     }
 
     /// Rotate frames foth
     pub fn rot_forth(&mut self, k: usize) {
+        self.map_delays(|delays| delays.rotate_right(k));
         self.frames.rot_forth(k);
     }
 
     /// Rotate frames back
     pub fn rot_back(&mut self, k: usize) {
+        self.map_delays(|delays| delays.rotate_left(k));
         self.frames.rot_back(k);
     }
 
+    /// Returns a copy of the art played at a constant frame rate.
+    /// Every frame of the copy lasts `1000 / fps` milliseconds, rounded to
+    /// a whole millisecond, and shows the frame displayed at its start, so
+    /// longer frames are repeated and shorter ones may be dropped. The total
+    /// duration is kept up to half a frame.
+    /// Fails if `fps` is not a positive finite number.
+    pub fn resample(&self, fps: f64) -> Result<Art> {
+        if !fps.is_finite() || fps <= 0.0 {
+            return Err(Error::FrameRate(fps));
+        }
+        let mut art = self.clone();
+        if self.frames() == 0 {
+            return Ok(art);
+        }
+        let delay = (1000.0 / fps).round().max(1.0) as usize;
+        let timeline = self.timeline();
+        let count = (timeline.duration_ms() as f64 / delay as f64).round() as usize;
+        let count = count.max(1);
+        art.frames.frames = (0..count)
+            .map(|i| {
+                let time = Duration::from_millis((i * delay) as u64);
+                let frame = timeline.frame_at(time).unwrap_or(self.frames() - 1);
+                self.frames.frames[frame].clone()
+            })
+            .collect();
+        art.header.delay = None;
+        art.set_global_delay(delay);
+        art.header.preview = self.get_preview_key().map(|preview| {
+            let start = timeline.timestamps_ms()[preview];
+            ((start + delay - 1) / delay).min(count - 1)
+        });
+        Ok(art)
+    }
+
+    /// Returns a copy of the art with runs of identical consecutive frames
+    /// merged into one frame lasting for the whole run, as [`Art::dedup`]
+    /// does in place. This undoes the repeats added by [`Art::resample`].
+    pub fn coalesce(&self) -> Art {
+        let mut art = self.clone();
+        art.dedup();
+        art
+    }
+
+    /// Scales all delays by `factor`: 2.0 plays at half speed and 0.5 at
+    /// double speed. Delays are rounded to whole milliseconds of at least 1ms.
+    /// Fails if `factor` is not a positive finite number.
+    pub fn scale_delays(&mut self, factor: f64) -> Result<()> {
+        let mut result = Ok(());
+        self.edit_delays(|delay| result = delay.scale(factor));
+        result
+    }
+
+    /// Clamps all delays into `min..=max` milliseconds.
+    pub fn clamp_delays(&mut self, min: usize, max: Option<usize>) {
+        self.edit_delays(|delay| delay.clamp(min, max));
+    }
+
+    /// Appends the frames before the last one in reverse order, down to the
+    /// second, so a loop plays back and forth without showing the first
+    /// and last frames twice in a row. Appended frames keep their delays.
+    pub fn ping_pong(&mut self) {
+        let frames = self.frames();
+        if frames < 3 {
+            return;
+        }
+        self.map_delays(|delays| {
+            let back: Vec<usize> = delays[1..frames - 1].iter().rev().copied().collect();
+            delays.extend(back);
+        });
+        let back: Vec<Frame> = self.frames.frames[1..frames - 1]
+            .iter()
+            .rev()
+            .cloned()
+            .collect();
+        self.frames.frames.extend(back);
+    }
+
+    /// Repeats the frames so the animation plays `times` times in a row.
+    /// A count of 0 is treated as 1.
+    pub fn repeat(&mut self, times: usize) {
+        let times = times.max(1);
+        self.map_delays(|delays| *delays = delays.repeat(times));
+        let frames = self.frames();
+        self.frames.frames = self
+            .frames
+            .frames
+            .iter()
+            .cycle()
+            .take(frames * times)
+            .cloned()
+            .collect();
+    }
+
     /// Crop art
     pub fn crop(&mut self, r_from: usize, r_to: usize, c_from: usize, c_to: usize) {
         self.frames.crop(r_from, r_to, c_from, c_to);
@@ -446,27 +586,45 @@ impl Art {
         }
     }
 
-    /// Sets the loop flag.
+    /// Sets the loop flag, removing the loop count.
     pub fn set_loop_key(&mut self, flag: bool) {
         if !flag || self.header.loop_comments.len() > 0 {
             self.header.loop_flag = Some(flag)
         } else {
             self.header.loop_flag = None
         }
+        self.header.loop_count = None;
     }
 
-    pub fn get_preview_key(&self) -> Option<usize> {
-        if let Some(preview) = self.header.preview {
-            if preview < self.frames() {
-                Some(preview)
-            } else {
-                None
+    /// Returns how many times the animation plays, `None` for forever.
+    /// Art that does not loop plays once; looping art plays as many times
+    /// as the `loop-count` key says, or forever without it.
+    pub fn get_loop_count(&self) -> Option<usize> {
+        if !self.get_loop_key() {
+            return Some(1);
+        }
+        self.header.loop_count.filter(|count| *count > 0)
+    }
+
+    /// Sets how many times the animation plays, `None` or 0 for forever.
+    /// A count of 1 turns the loop flag off; larger counts are kept in the
+    /// `loop-count` key.
+    pub fn set_loop_count(&mut self, count: Option<usize>) {
+        match count.filter(|count| *count > 0) {
+            Some(1) => self.set_loop_key(false),
+            count => {
+                self.set_loop_key(true);
+                self.header.loop_count = count;
             }
-        } else {
-            None
         }
     }
 
+    pub fn get_preview_key(&self) -> Option<usize> {
+        self.header
+            .preview
+            .filter(|preview| *preview < self.frames())
+    }
+
     pub fn set_preview_key(&mut self, preview: Option<usize>) {
         if let Some(preview) = preview {
             if preview < self.frames() {
@@ -495,6 +653,37 @@ impl Art {
         }
     }
 
+    /// Returns the playback timing of all frames.
+    pub fn timeline(&self) -> Timeline {
+        Timeline::new(&self.frame_delays())
+    }
+
+    /// Returns the time a frame starts at.
+    pub fn start_time(&self, frame: usize) -> Option<Duration> {
+        self.timeline().start_time(frame)
+    }
+
+    /// Returns the frame shown at `time` of playback.
+    /// Art starts over after its end as many times as its loop count says,
+    /// then keeps showing the last frame. Returns `None` if there are no frames.
+    pub fn frame_at(&self, time: Duration) -> Option<usize> {
+        let timeline = self.timeline();
+        let total = timeline.duration_ms() as u128;
+        if total == 0 {
+            return None;
+        }
+        let playing = match self.get_loop_count() {
+            Some(plays) => time.as_millis() < total * plays as u128,
+            None => true,
+        };
+        let time = if playing {
+            Duration::from_millis((time.as_millis() % total) as u64)
+        } else {
+            time
+        };
+        timeline.frame_at(time).or(Some(timeline.frames() - 1))
+    }
+
     /// Sets the global delay.
     pub fn set_global_delay(&mut self, global: usize) {
         if let Some(d) = &mut self.header.delay {
@@ -694,6 +883,11 @@ impl Art {
 
     /// Removes a frame at the given index.
     pub fn remove_frame(&mut self, frame: usize) {
+        self.map_delays(|delays| {
+            if frame < delays.len() {
+                delays.remove(frame);
+            }
+        });
         self.frames.remove_frame(frame);
     }
 
@@ -703,20 +897,44 @@ impl Art {
     }
 
     /// Duplicates a frame, inserting the copy after the original.
+    /// The copy gets the delay of the original.
     pub fn dup_frame(&mut self, frame: usize) {
+        let global = self.get_global_delay();
+        self.map_delays(|delays| {
+            if frame >= delays.len() {
+                delays.resize(frame + 1, global);
+            }
+            delays.insert(frame, delays[frame]);
+        });
         self.frames.dup_frame(frame);
     }
+
+    /// Applies a change of frame order to the frame delays.
+    fn map_delays<F: FnOnce(&mut Vec<usize>)>(&mut self, f: F) {
+        let frames = self.frames();
+        self.edit_delays(|delay| delay.map_frames(frames, f));
+    }
+
+    /// Edits the frame delays. A delay header is only added when the edit
+    /// changes timing from the default.
+    fn edit_delays<F: FnOnce(&mut Delay)>(&mut self, f: F) {
+        let added = self.header.delay.is_none();
+        let delay = self.header.delay.get_or_insert_with(|| Delay {
+            global: 50,
+            per_frame: HashMap::new(),
+        });
+        f(delay);
+        if added && delay.per_frame.is_empty() && delay.global == 50 {
+            self.header.delay = None;
+        }
+    }
 }
 
 // Conversions
 impl Art {
     /// Returns the total duration of the animation in seconds.
     pub fn duration(&self) -> f64 {
-        let mut dur: usize = 0;
-        for f in 0..self.frames() {
-            dur += self.get_frame_delay(f);
-        }
-        dur as f64 / 1000.0
+        self.timeline().duration_ms() as f64 / 1000.0
     }
 
     /// Converts the art to json document with extra metadata
@@ -783,6 +1001,10 @@ impl Art {
             json_quote(&(self.header.license.clone().unwrap_or("proprietary".into())))
         );
         json += &format!("    \"loop\": {},\n", self.get_loop_key());
+        match self.header.loop_count {
+            Some(count) => json += &format!("    \"loop-count\": {},\n", count),
+            None => json += "    \"loop-count\": null,\n",
+        }
         json += &format!("    \"preview\": {},\n", self.header.preview.unwrap_or(0));
         json += &format!("    \"colors\": {},\n", self.color());
         json += "    \"palette\": {";
@@ -911,6 +1133,144 @@ impl Art {
         json
     }
 
+    /// Reads art from a json document in the format written by
+    /// [`Art::to_json`], described by [`JSON_SCHEMA`].
+    /// The `meta` object is derived data and is only checked against the
+    /// frames when they are empty. Palette entries equal to builtin colors,
+    /// the default license, loop flag and preview, and the `colors` flag
+    /// when it matches the content are not written back to the header.
+    pub fn from_json(text: &str) -> Result<Self> {
+        let value = json::parse(text)?;
+        let header = value
+            .get("header")
+            .ok_or_else(|| schema_error("header", "missing"))?;
+        let frames = json_array(&value, "frames")?;
+
+        let mut art = Self::new(0, 0, 0, Cell::default());
+        for (f, frame) in frames.iter().enumerate() {
+            let rows = |key: &str| -> Result<Vec<&str>> {
+                json_array(frame, key)?
+                    .iter()
+                    .map(|row| {
+                        row.as_str().ok_or_else(|| {
+                            schema_error(&format!("frames[{}].{}", f, key), "expected strings")
+                        })
+                    })
+                    .collect()
+            };
+            let frame = Frame::from_rows(&rows("text")?, &rows("colors")?)?;
+            if f == 0 {
+                art.frames.width = frame.width();
+                art.frames.height = frame.height();
+            } else if frame.width() != art.frames.width {
+                return Err(Error::WidthMismatch);
+            } else if frame.height() != art.frames.height {
+                return Err(Error::HeightMismatch);
+            }
+            art.frames.frames.push(frame);
+        }
+        if frames.is_empty() {
+            if let Some(meta) = value.get("meta") {
+                art.frames.width = json_usize(meta, "width")?.unwrap_or(0);
+                art.frames.height = json_usize(meta, "height")?.unwrap_or(0);
+            }
+        }
+        let delays = frames
+            .iter()
+            .map(|frame| Ok(json_usize(frame, "delay")?.unwrap_or(50)))
+            .collect::<Result<Vec<usize>>>()?;
+        if delays.iter().any(|delay| *delay != 50) {
+            art.header.delay = Some(Delay::from_vec_delays(&delays));
+        }
+
+        art.header.title = json_string(header, "title")?;
+        art.header.src = json_string(header, "src")?;
+        art.header.editor = json_string(header, "editor")?;
+        art.header.license = json_string(header, "license")?.filter(|l| l != "proprietary");
+        for author in json_strings(header, "authors")? {
+            art.add_author(author);
+        }
+        for author in json_strings(header, "orig-authors")? {
+            art.add_orig_author(author);
+        }
+        for tag in json_strings(header, "tags")? {
+            art.add_tag(tag);
+        }
+        for line in json_strings(header, "extra-keys")? {
+            let line = normalize_text(line);
+            if line.is_empty() || line.starts_with('@') || line.starts_with(";;") {
+                return Err(schema_error("header.extra-keys", "expected key lines"));
+            }
+            art.header.extra_keys.push(ExtraHeaderKey {
+                line,
+                comments: Vec::new(),
+            });
+        }
+        match header.get("loop") {
+            None | Some(JsonValue::Null) => {}
+            Some(JsonValue::Bool(flag)) => art.set_loop_key(*flag),
+            Some(_) => return Err(schema_error("header.loop", "expected bool")),
+        }
+        art.header.loop_count = json_usize(header, "loop-count")?;
+        if let Some(preview) = json_usize(header, "preview")? {
+            if preview > 0 {
+                art.set_preview_key(Some(preview));
+            }
+        }
+        if let Some(palette) = header.get("palette") {
+            let palette = match palette {
+                JsonValue::Object(palette) => palette,
+                _ => return Err(schema_error("header.palette", "expected object")),
+            };
+            let builtin = Header::default();
+            for (name, pair) in palette {
+                let path = format!("header.palette.{}", name);
+                let mut chars = name.chars();
+                let name = match (chars.next(), chars.next()) {
+                    (Some(name), None) => Char::new(name)?,
+                    _ => return Err(schema_error(&path, "expected single character name")),
+                };
+                let color = |key: &str| -> Result<Color> {
+                    match json_string(pair, key)? {
+                        Some(color) if !color.is_empty() => color.parse(),
+                        _ => Ok(Color::None),
+                    }
+                };
+                let pair = ColorPair {
+                    fg: color("fg")?,
+                    bg: color("bg")?,
+                };
+                if builtin.get_color_map(name) != pair {
+                    art.set_color_map(name, pair);
+                }
+            }
+        }
+        match header.get("colors") {
+            None | Some(JsonValue::Null) => {}
+            Some(JsonValue::Bool(colors)) => {
+                if art.color() != *colors {
+                    art.header.colors = Some(*colors);
+                }
+            }
+            Some(_) => return Err(schema_error("header.colors", "expected bool")),
+        }
+
+        art.attached = json_string(&value, "attached")?;
+        for (i, block) in json_array(&value, "extra-blocks")?.iter().enumerate() {
+            let path = format!("extra-blocks[{}]", i);
+            let title = json_string(block, "title")?.map(|title| normalize_text(&title));
+            let title = match title {
+                Some(title) if !title.is_empty() && !title.contains(' ') => title,
+                _ => return Err(schema_error(&path, "expected title")),
+            };
+            art.extra.push(ExtraBlock {
+                title,
+                content: json_string(block, "content")?.unwrap_or_default(),
+            });
+        }
+        Ok(art)
+    }
+
     /// Converts the art to durformat
     pub fn to_dur(&self) -> String {
         let mut dur = String::from(
@@ -991,7 +1351,7 @@ impl Art {
     }
 
     fn to_asciicast2_internal(&self) -> (String, f64) {
-        let dur = self.duration();
+        let dur = (self.timeline().duration_ms() * self.recorded_plays()) as f64 / 1000.0;
         let mut cast = match self.header.title {
             Some(_) => format!(
                 "{{\"version\": 2, \"width\": {}, \"height\": {}, \"duration\": {}, \"title\": {} }}\n",
@@ -1007,28 +1367,249 @@ impl Art {
                 dur
             ),
         };
-        cast += format!("[0, \"o\", {}]\n", json_quote("\x1b[?25l")).as_str();
-        let mut cum_time: usize = 0;
-        let color = self.color();
-        let h = self.height();
-        let h = if h > 1 { h - 1 } else { h };
-        for f in 0..self.frames() {
-            let frame = &self.frames.frames[f];
-            let time = (cum_time as f64) / 1000.0;
-            let ansi = frame.ansi(&self.header.palette, color);
-            let ansi = ansi.replace("\n", "\n\r") + format!("\r\x1b[{}A", h).as_str();
-            cast += format!("[{}, \"o\", {}]\n", time, json_quote(&ansi)).as_str();
-            cum_time += self.get_frame_delay(f)
-        }
-        cast += format!("[{}, \"o\", {}]\n", dur, json_quote(&"\n".repeat(h))).as_str();
+        for (time, data) in self.asciicast_events() {
+            let time = (time as f64) / 1000.0;
+            cast += format!("[{}, \"o\", {}]\n", time, json_quote(&data)).as_str();
+        }
         (cast, dur)
     }
 
+    /// Converts the art to ASCIIcast v3 format string.
+    pub fn to_asciicast3(&self) -> String {
+        let mut cast = self.to_asciicast3_internal();
+        cast += format!("[0, \"o\", {}]\n", json_quote("\x1b[?25h")).as_str();
+        cast
+    }
+
+    /// Converts the art to ASCIIcast v3 format string with disabled cursor.
+    pub fn to_asciicast3_no_cursor(&self) -> String {
+        self.to_asciicast3_internal()
+    }
+
+    fn to_asciicast3_internal(&self) -> String {
+        let term = format!(
+            "\"term\": {{\"cols\": {}, \"rows\": {}}}",
+            self.width(),
+            self.height()
+        );
+        let mut cast = match self.header.title {
+            Some(_) => format!(
+                "{{\"version\": 3, {}, \"title\": {}}}\n",
+                term,
+                json_quote(&self.title_line())
+            ),
+            None => format!("{{\"version\": 3, {}}}\n", term),
+        };
+        let mut last: usize = 0;
+        for (time, data) in self.asciicast_events() {
+            let interval = ((time - last) as f64) / 1000.0;
+            cast += format!("[{}, \"o\", {}]\n", interval, json_quote(&data)).as_str();
+            last = time;
+        }
+        cast
+    }
+
+    /// Returns the output events shared by asciicast exporters
+    /// as (absolute time in ms, data) pairs.
+    /// Frames are repeated for every play, see [`Art::recorded_plays`].
+    fn asciicast_events(&self) -> Vec<(usize, String)> {
+        let mut events = vec![(0, String::from("\x1b[?25l"))];
+        let timeline = self.timeline();
+        let color = self.color();
+        // Rows between the first and the last one of the art.
+        let h = self.height().saturating_sub(1);
+        let frames: Vec<String> = self
+            .frames
+            .frames
+            .iter()
+            .map(|frame| {
+                let ansi = frame.ansi(&self.header.palette, color);
+                let mut ansi = ansi.replace("\n", "\n\r") + "\r";
+                if h > 0 {
+                    ansi += &format!("\x1b[{}A", h);
+                }
+                ansi
+            })
+            .collect();
+        let total = timeline.duration_ms();
+        let plays = self.recorded_plays();
+        for play in 0..plays {
+            for (ansi, start) in frames.iter().zip(timeline.timestamps_ms()) {
+                events.push((play * total + start, ansi.clone()));
+            }
+        }
+        // Moves the cursor to the last row of the art without scrolling.
+        events.push((plays * total, "\n".repeat(h)));
+        events
+    }
+
+    /// Returns how many times recordings without a loop mechanism play the
+    /// animation: the loop count, or once for art looping forever.
+    fn recorded_plays(&self) -> usize {
+        self.get_loop_count().unwrap_or(1)
+    }
+
     /// Converts the art to an SVG frames string using the given CSS color map and font.
     pub fn to_svg_frames(&self, map: &CSSColorMap, font: &Font) -> String {
+        self.to_svg_frames_with(map, font, &SvgOptions::default())
+    }
+
+    /// Converts the art to an SVG frames string using the given CSS color map,
+    /// font and export options.
+    /// Without `plays` in the options the art loop count is used.
+    pub fn to_svg_frames_with(
+        &self,
+        map: &CSSColorMap,
+        font: &Font,
+        options: &SvgOptions,
+    ) -> String {
         let delay = self.header.delay.clone().unwrap_or(Delay::default());
+        self.frames.to_svg_frames_with(
+            self.color(),
+            &self.header.palette,
+            map,
+            font,
+            &delay,
+            &SvgOptions {
+                plays: options.plays.or(self.get_loop_count()),
+                ..options.clone()
+            },
+        )
+    }
+
+    /// Renders the art to an animated GIF.
+    /// Cells take the geometry of `font`, glyphs are drawn from `glyphs`
+    /// and colors are resolved to RGB through `map`.
+    pub fn to_gif(&self, map: &CSSColorMap, font: &Font, glyphs: &BitmapFont) -> Vec<u8> {
+        gif::encode(
+            &self.render_frames(map, font, glyphs),
+            &gif::delays_to_centiseconds(&self.frame_delays()),
+            self.get_loop_count(),
+        )
+    }
+
+    /// Renders the preview frame (or the first one) to a PNG image.
+    /// Uses the same geometry and colors as [`Art::to_gif`].
+    pub fn to_png(&self, map: &CSSColorMap, font: &Font, glyphs: &BitmapFont) -> Vec<u8> {
+        let frame = self.get_preview_key().unwrap_or(0);
+        match self.frames.frames.get(frame) {
+            Some(frame) => frame.to_png(self.color(), &self.header.palette, map, font, glyphs),
+            None => png::encode(&Canvas::new(0, 0, (0, 0, 0))),
+        }
+    }
+
+    /// Renders the art to an animated PNG.
+    /// Uses the same geometry and colors as [`Art::to_gif`].
+    pub fn to_apng(&self, map: &CSSColorMap, font: &Font, glyphs: &BitmapFont) -> Vec<u8> {
+        let plays = self
+            .get_loop_count()
+            .map(|plays| plays.min(u32::MAX as usize) as u32)
+            .unwrap_or(0);
+        png::encode_animated(
+            &self.render_frames(map, font, glyphs),
+            &self.frame_delays(),
+            plays,
+        )
+    }
+
+    fn render_frames(&self, map: &CSSColorMap, font: &Font, glyphs: &BitmapFont) -> Vec<Canvas> {
         self.frames
-            .to_svg_frames(self.color(), &self.header.palette, map, font, &delay)
+            .frames
+            .iter()
+            .map(|frame| render_frame(frame, self.color(), &self.header.palette, map, font, glyphs))
+            .collect()
+    }
+
+    fn frame_delays(&self) -> Vec<usize> {
+        (0..self.frames())
+            .map(|f| self.get_frame_delay(f))
+            .collect()
+    }
+
+    /// Converts the art to a self-contained HTML page with frames, palette and
+    /// delays inlined as data and a small JS player with play/pause, frame
+    /// scrubbing, speed control and loop toggle.
+    /// The preview frame is shown as a poster and the title line as a caption.
+    pub fn to_html_player(&self, map: &CSSColorMap, font: &Font) -> String {
+        let color = self.color();
+        let mut classes = String::new();
+        let mut class_map = Vec::new();
+        if color {
+            let mut names: Vec<Char> = Vec::new();
+            for frame in &self.frames.frames {
+                for name in frame.rows.iter().flatten().filter_map(|c| c.color) {
+                    if !names.contains(&name) {
+                        names.push(name);
+                    }
+                }
+            }
+            for (i, name) in names.iter().enumerate() {
+                let pair = self.header.palette.get_color(*name);
+                classes += &format!(
+                    ".a3-c{} {{ color: {}; background: {}; }}\n",
+                    i,
+                    map.map(pair.fg, true),
+                    map.map(pair.bg, false)
+                );
+                class_map.push(format!("{}: {}", json_quote(&name.to_string()), i));
+            }
+        }
+        let mut frames = Vec::new();
+        for frame in &self.frames.frames {
+            let text: Vec<String> = frame
+                .rows
+                .iter()
+                .map(|row| json_quote(&row.iter().map(|c| c.text.char).collect::<String>()))
+                .collect();
+            let mut json = format!("{{\"text\": [{}]", text.join(", "));
+            if color {
+                let colors: Vec<String> = frame
+                    .rows
+                    .iter()
+                    .map(|row| {
+                        let row: String = row
+                            .iter()
+                            .map(|c| c.color.map(|c| c.char).unwrap_or(' '))
+                            .collect();
+                        json_quote(&row)
+                    })
+                    .collect();
+                json += &format!(", \"color\": [{}]", colors.join(", "));
+            }
+            frames.push(json + "}");
+        }
+        let delays: Vec<String> = self.frame_delays().iter().map(|d| d.to_string()).collect();
+        // Art playing once gets no count, so ticking the loop box loops forever.
+        let plays = match self.get_loop_count() {
+            Some(plays) if plays > 1 => plays.to_string(),
+            _ => "null".to_string(),
+        };
+        let data = format!(
+            "{{\"delays\": [{}], \"loop\": {}, \"plays\": {}, \"preview\": {}, \"classes\": {{{}}}, \"frames\": [{}]}}",
+            delays.join(", "),
+            self.get_loop_key(),
+            plays,
+            self.get_preview_key().unwrap_or(0),
+            class_map.join(", "),
+            frames.join(",\n"),
+        )
+        // Keeps art text from closing the script element.
+        .replace("</", "<\\/");
+        let title = escape_html(&self.title_line());
+        fill_template(
+            include_str!("player.html"),
+            &[
+                ("title", &title),
+                ("caption", &title),
+                ("font", &escape_html(&font.family)),
+                ("font_size", &font.size.to_string()),
+                ("line_height", &font.height.to_string()),
+                ("fg", &map.map(Color::None, true)),
+                ("bg", &map.map(Color::None, false)),
+                ("classes", &classes),
+                ("data", &data),
+            ],
+        )
     }
 
     /// Returns a vector of ANSI-encoded strings for each frame.
@@ -1063,35 +1644,110 @@ impl Art {
         write!(file, "{}", self)
     }
 
+    /// Converts the art to legacy 3a format.
+    /// Fails if a color in use is not one of the 16 basic colors, which are
+    /// all legacy format can express, or if frames have their own delays.
+    /// Header keys without legacy counterparts, attached content and extra
+    /// blocks are dropped.
+    pub fn to_legacy_string(&self) -> Result<String> {
+        if let Some(delay) = &self.header.delay {
+            if !delay.per_frame.is_empty() {
+                return Err(Error::LegacyDelay);
+            }
+        }
+        let info = LegacyHeaderInfo {
+            colors: self.legacy_color_mode(),
+            width: self.width(),
+            height: self.height(),
+        };
+        let utf8 = self
+            .frames
+            .frames
+            .iter()
+            .flat_map(|frame| frame.rows.iter().flatten())
+            .any(|cell| !cell.text.char.is_ascii());
+        let mut out = Fmt(|f| self.header.fmt_legacy(f, info, utf8)).to_string();
+        for frame in &self.frames.frames {
+            frame.write_legacy(&mut out, info.colors, &self.header.palette)?;
+            out.push('\n');
+        }
+        Ok(out)
+    }
+
+    /// Writes the art to a file in legacy 3a format.
+    /// See [`Art::to_legacy_string`].
+    pub fn to_legacy_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        std::fs::write(path, self.to_legacy_string()?)?;
+        Ok(())
+    }
+
+    /// Picks the legacy color mode covering the channels used by frames.
+    fn legacy_color_mode(&self) -> LegacyColorMode {
+        if !self.color() {
+            return LegacyColorMode::None;
+        }
+        let pairs = self
+            .frames
+            .frames
+            .iter()
+            .flat_map(|frame| frame.rows.iter().flatten())
+            .map(|cell| cell.to_pair(&self.header.palette));
+        let (mut fg, mut bg) = (false, false);
+        for pair in pairs {
+            fg |= pair.fg != Color::None;
+            bg |= pair.bg != Color::None;
+        }
+        match (fg, bg) {
+            (false, false) => LegacyColorMode::None,
+            (true, false) => LegacyColorMode::FgOnly,
+            (false, true) => LegacyColorMode::BgOnly,
+            (true, true) => LegacyColorMode::FgAndBg,
+        }
+    }
+
     /// Consumes the art and returns its components: header, frames, attached, extra.
     pub fn to_components(self) -> (Header, Frames, Option<String>, Vec<ExtraBlock>) {
         (self.header, self.frames, self.attached, self.extra)
     }
 
+    /// Converts the art to a ttyrec recording.
+    /// Every frame is drawn over the previous one by moving the cursor back
+    /// to the top-left corner of the art. A final empty record marks the end
+    /// of the last frame.
     pub fn to_ttyrec(&self) -> Vec<u8> {
         let mut v = Vec::new();
-        let mut delay_acum: usize = 0;
-        for (f, frame) in self.frames.frames.iter().enumerate() {
-            let mut text = frame.ansi(&self.header.palette, self.color());
-            if f == 0 {
-                text += &format!("\x1b]0;{}\x07", self.title_line());
+        let timeline = self.timeline();
+        let h = self.height();
+        let plays = self.recorded_plays();
+        for play in 0..plays {
+            for (f, frame) in self.frames.frames.iter().enumerate() {
+                let mut text = String::new();
+                if play == 0 && f == 0 {
+                    text += &format!("\x1b]0;{}\x07", self.title_line());
+                } else {
+                    text += "\r";
+                    if h > 1 {
+                        text += &format!("\x1b[{}A", h - 1);
+                    }
+                }
+                text += &frame
+                    .ansi(&self.header.palette, self.color())
+                    .replace("\n", "\r\n");
+                if play + 1 == plays && f + 1 == self.frames() {
+                    text += "\r\n";
+                }
+                let tf = TtyrecFrame {
+                    timestamp_ms: play * timeline.duration_ms() + timeline.timestamps_ms()[f],
+                    text,
+                };
+                tf.append_to_vec(&mut v);
             }
-            text += &format!("\x1b[{}A\n", self.height());
-            let tf = TtyrecFrame {
-                timestamp_ms: delay_acum,
-                text: text,
-            };
-            delay_acum += self.get_frame_delay(f);
-            tf.append_to_vec(&mut v);
         }
+        // Marks the end of the last frame without changing the screen.
         if self.frames() > 0 {
-            let f = self.frames() - 1;
-            let frame = &self.frames.frames[f];
-            let mut text = frame.ansi(&self.header.palette, self.color());
-            text += "\n";
             let tf = TtyrecFrame {
-                timestamp_ms: delay_acum,
-                text: text,
+                timestamp_ms: plays * timeline.duration_ms(),
+                text: String::new(),
             };
             tf.append_to_vec(&mut v);
         }
@@ -1113,32 +1769,166 @@ impl Art {
         })
     }
 
-    /// Converts ttyrec anomation to Art
+    /// Converts ttyrec anomation to Art, emulating an 80x24 terminal.
     pub fn from_ttyrec<R: Read>(reader: R) -> Result<Self> {
-        let mut a = Self::new(1, 10, 10, Cell::default());
-        let mut max_width: usize = 0;
-        let mut max_height: usize = 0;
-        let mut frames: Vec<Vec<Vec<Cell>>> = vec![];
+        Self::from_ttyrec_with(reader, &TtyrecOptions::default())
+    }
+
+    /// Converts ttyrec anomation to Art.
+    /// Payloads are replayed through a [`Terminal`] and the screen is
+    /// captured after each of them. Frame delays are taken from the
+    /// differences between timestamps and limited as configured in
+    /// `options`; a last record that leaves the screen unchanged only ends
+    /// the previous frame. The result is cropped to the screen area that
+    /// was drawn on.
+    pub fn from_ttyrec_with<R: Read>(reader: R, options: &TtyrecOptions) -> Result<Self> {
+        let mut term = Terminal::new(options.width, options.height);
+        let mut art = Self::new(0, term.width(), term.height(), Cell::default());
+        let mut timestamps = Vec::new();
         for raw in TtyrecReader::new(reader) {
             let raw = raw?;
-            let mut frame: Vec<Vec<Cell>> = vec![];
-            for line in raw.text.lines() {
-                let row = parse_ansi_line(line, &mut a);
-                max_width = max_width.max(row.len());
-                frame.push(row);
+            term.feed(&raw.text);
+            let frame = term.snapshot(&mut art);
+            art.frames.frames.push(frame);
+            timestamps.push(raw.timestamp_ms);
+        }
+        // A trailing record that does not change the screen only marks the
+        // end of the last frame.
+        let end = timestamps.last().copied().unwrap_or(0);
+        let f = art.frames.frames.len();
+        if f > 1 && art.frames.frames[f - 1] == art.frames.frames[f - 2] {
+            art.frames.frames.pop();
+            timestamps.pop();
+        }
+        let (width, height) = term.used_size();
+        art.frames.resize(width, height, Cell::default());
+        for frame in art.frames.frames.iter_mut() {
+            frame.recalc_colors();
+        }
+        let mut delays = delays_from_timestamps(&timestamps);
+        if let (Some(last), Some(start)) = (delays.last_mut(), timestamps.last()) {
+            *last = end.saturating_sub(*start);
+        }
+        art.set_recorded_delays(
+            delays,
+            options.min_delay,
+            options.max_delay,
+            options.collapse_idle,
+        );
+        Ok(art)
+    }
+
+    /// Converts asciicast v2 recording to Art.
+    pub fn from_asciicast2<R: Read>(reader: R) -> Result<Self> {
+        Self::from_asciicast2_with(reader, &AsciicastOptions::default())
+    }
+
+    /// Converts asciicast v2 recording to Art.
+    /// Output events are replayed through a [`Terminal`] of the size given
+    /// in the cast header, producing one frame per event or per `quantum`.
+    /// Title and delays are taken from the recording.
+    pub fn from_asciicast2_with<R: Read>(reader: R, options: &AsciicastOptions) -> Result<Self> {
+        let (header, events) = asciicast::read_v2(reader)?;
+        Ok(Self::from_cast_events(header, events, options))
+    }
+
+    /// Converts asciicast v3 recording to Art.
+    pub fn from_asciicast3<R: Read>(reader: R) -> Result<Self> {
+        Self::from_asciicast3_with(reader, &AsciicastOptions::default())
+    }
+
+    /// Converts asciicast v3 recording to Art.
+    /// Works the same way as [`Art::from_asciicast2_with`].
+    pub fn from_asciicast3_with<R: Read>(reader: R, options: &AsciicastOptions) -> Result<Self> {
+        let (header, events) = asciicast::read_v3(reader)?;
+        Ok(Self::from_cast_events(header, events, options))
+    }
+
+    fn from_cast_events(
+        header: CastHeader,
+        events: Vec<CastEvent>,
+        options: &AsciicastOptions,
+    ) -> Self {
+        let mut term = Terminal::new(header.width, header.height);
+        let mut art = Self::new(0, term.width(), term.height(), Cell::default());
+        let mut timestamps: Vec<usize> = Vec::new();
+        let mut pending = false;
+        let mut end = header.duration.unwrap_or(0);
+        for event in &events {
+            end = end.max(event.time_ms);
+            if event.code != "o" {
+                continue;
             }
-            max_height = max_height.max(frame.len());
-            frames.push(frame);
+            if let Some(start) = timestamps.last() {
+                if pending && event.time_ms.saturating_sub(*start) >= options.quantum.max(1) {
+                    let frame = term.snapshot(&mut art);
+                    art.frames.frames.push(frame);
+                    pending = false;
+                }
+            }
+            if !pending {
+                timestamps.push(event.time_ms);
+                pending = true;
+            }
+            term.feed(&event.data);
         }
-        let mut art = Self::new(frames.len(), max_width, max_height, Cell::default());
-        for (f, frame) in frames.iter().enumerate() {
-            for (r, row) in frame.iter().enumerate() {
-                for (c, cell) in row.iter().enumerate() {
-                    art.set(f, c, r, *cell);
+        if pending {
+            let frame = term.snapshot(&mut art);
+            art.frames.frames.push(frame);
+        }
+        // Trailing events that do not change the screen only mark the end
+        // of the last frame.
+        let mut f = art.frames.frames.len();
+        while f > 1 && art.frames.frames[f - 1] == art.frames.frames[f - 2] {
+            art.frames.frames.pop();
+            timestamps.pop();
+            f -= 1;
+        }
+        let mut delays = delays_from_timestamps(&timestamps);
+        if let (Some(last), Some(start)) = (delays.last_mut(), timestamps.last()) {
+            *last = end.saturating_sub(*start);
+        }
+        art.header.title = header.title;
+        art.set_recorded_delays(
+            delays,
+            options.min_delay,
+            options.max_delay.or(header.idle_time_limit),
+            options.collapse_idle,
+        );
+        art
+    }
+
+    /// Applies delays measured from a recording to the frames.
+    /// A zero delay for the last frame means it is unknown and falls back
+    /// to the global one.
+    fn set_recorded_delays(
+        &mut self,
+        mut delays: Vec<usize>,
+        min: usize,
+        max: Option<usize>,
+        collapse_idle: bool,
+    ) {
+        if collapse_idle {
+            let mut f = 1;
+            while f < self.frames.frames.len() {
+                if self.frames.frames[f] == self.frames.frames[f - 1] {
+                    self.frames.frames.remove(f);
+                    let d = delays.remove(f);
+                    delays[f - 1] += d;
+                } else {
+                    f += 1;
                 }
             }
         }
-        Ok(art)
+        let last = delays.len().saturating_sub(1);
+        for (f, d) in delays.iter_mut().enumerate() {
+            if f < last || *d > 0 {
+                *d = clamp_delay(*d, min, max);
+            }
+        }
+        if delays.len() > 1 {
+            self.header.delay = Some(Delay::from_vec_delays(&delays));
+        }
     }
 
     /// Constructs art from plain text with ANSI color escape codes.
@@ -1168,13 +1958,32 @@ impl Art {
 
     /// Reads an Art from any reader.
     pub fn from_reader<R: Read>(r: R) -> Result<Self> {
-        let mut lines = BufReader::new(r).lines();
-        Self::from_lines(&mut lines)
+        Self::read_lines(&mut Lines::from_reader(r))
+    }
+
+    /// Reads an Art from any reader with the given options.
+    /// Returns the art along with the fixes applied in lenient mode.
+    pub fn from_reader_with<R: Read>(r: R, options: &ParseOptions) -> Result<(Self, Vec<Fix>)> {
+        let mut lines = Lines::with_options(BufReader::new(r).lines(), options.clone());
+        let art = Self::read_lines(&mut lines)?;
+        Ok((art, lines.into_fixes()))
     }
 
     /// Reads an Art from an iterator of lines.
     pub fn from_lines<R: Read>(lines: &mut io::Lines<BufReader<R>>) -> Result<Self> {
-        let header = Header::read(lines)?;
+        Self::read_lines(&mut Lines::new(lines))
+    }
+
+    fn read_lines<I: Iterator<Item = io::Result<String>>>(lines: &mut Lines<I>) -> Result<Self> {
+        Self::read_lines_with_blocks(lines, &mut Vec::new())
+    }
+
+    /// Reads an Art, recording the title and line number of every block.
+    pub(crate) fn read_lines_with_blocks<I: Iterator<Item = io::Result<String>>>(
+        lines: &mut Lines<I>,
+        blocks: &mut Vec<(String, usize)>,
+    ) -> Result<Self> {
+        let mut header = Header::read_lines(lines)?;
         let mut frames = Frames {
             text_pin: None,
             color_pin: None,
@@ -1185,30 +1994,35 @@ impl Art {
         let mut attached: Option<String> = None;
         let mut extra: Vec<ExtraBlock> = Vec::new();
         if let Some(legacy) = header.legacy {
-            frames = Frames::read_legacy(legacy, lines)?;
+            frames = Frames::read_legacy(legacy, lines, &mut header)?;
         } else {
             loop {
                 let title = next_block(lines);
                 match title {
-                    Ok(Some(blk)) => match blk.as_str() {
-                        "attach" => {
-                            if let Some(line) = lines.next() {
-                                attached = Some(line?);
+                    Ok(Some(blk)) => {
+                        blocks.push((blk.clone(), lines.line()));
+                        match blk.as_str() {
+                            "attach" => {
+                                if let Some(line) = lines.next() {
+                                    attached = Some(line?);
+                                }
+                            }
+                            "text-pin" => {
+                                frames.read_text_pin(lines)?;
+                            }
+                            "color-pin" => {
+                                frames.read_color_pin(lines)?;
+                            }
+                            "body" => {
+                                frames.read_body(lines, &header)?;
+                            }
+                            title => {
+                                let block = ExtraBlock::read(title, lines)
+                                    .map_err(|err| lines.locate_block(err, title, None))?;
+                                extra.push(block);
                             }
                         }
-                        "text-pin" => {
-                            frames.read_text_pin(lines)?;
-                        }
-                        "color-pin" => {
-                            frames.read_color_pin(lines)?;
-                        }
-                        "body" => {
-                            frames.read_body(lines, &header)?;
-                        }
-                        title => {
-                            extra.push(ExtraBlock::read(title, lines)?);
-                        }
-                    },
+                    }
                     Ok(None) => {
                         break;
                     }
@@ -1287,13 +2101,17 @@ impl fmt::Display for Art {
 
 /// An extra block in the 3a file format with a title and content.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ExtraBlock {
     pub title: String,
     pub content: String,
 }
 
 impl ExtraBlock {
-    pub(crate) fn read<R: Read>(title: &str, lines: &mut io::Lines<BufReader<R>>) -> Result<Self> {
+    pub(crate) fn read<I: Iterator<Item = io::Result<String>>>(
+        title: &str,
+        lines: &mut Lines<I>,
+    ) -> Result<Self> {
         let mut block = ExtraBlock {
             title: title.into(),
             content: "".into(),
@@ -1318,20 +2136,63 @@ impl fmt::Display for ExtraBlock {
     }
 }
 
-pub(crate) fn next_block<R: Read>(lines: &mut io::Lines<BufReader<R>>) -> Result<Option<String>> {
-    for line in lines {
+pub(crate) fn next_block<I: Iterator<Item = io::Result<String>>>(
+    lines: &mut Lines<I>,
+) -> Result<Option<String>> {
+    while let Some(line) = lines.next() {
         let line = normalize_text(line?.as_str());
         if line.is_empty() {
             continue;
         }
         return match line.strip_prefix("@") {
             Some(name) => Ok(Some(name.into())),
-            None => Err(Error::BlockExpected(line)),
+            None => Err(lines.locate(Error::BlockExpected(line), Some(1))),
         };
     }
     Ok(None)
 }
 
+fn schema_error(path: &str, msg: &str) -> Error {
+    Error::JsonParsing(format!("{}: {}", path, msg))
+}
+
+/// Returns an array value of an object, empty if the key is missing or null.
+fn json_array<'a>(value: &'a JsonValue, key: &str) -> Result<&'a [JsonValue]> {
+    match value.get(key) {
+        None | Some(JsonValue::Null) => Ok(&[]),
+        Some(JsonValue::Array(array)) => Ok(array),
+        Some(_) => Err(schema_error(key, "expected array")),
+    }
+}
+
+fn json_strings<'a>(value: &'a JsonValue, key: &str) -> Result<Vec<&'a str>> {
+    json_array(value, key)?
+        .iter()
+        .map(|item| {
+            item.as_str()
+                .ok_or_else(|| schema_error(key, "expected strings"))
+        })
+        .collect()
+}
+
+fn json_string(value: &JsonValue, key: &str) -> Result<Option<String>> {
+    match value.get(key) {
+        None | Some(JsonValue::Null) => Ok(None),
+        Some(JsonValue::String(s)) => Ok(Some(s.clone())),
+        Some(_) => Err(schema_error(key, "expected string")),
+    }
+}
+
+fn json_usize(value: &JsonValue, key: &str) -> Result<Option<usize>> {
+    match value.get(key) {
+        None | Some(JsonValue::Null) => Ok(None),
+        Some(n) => n
+            .as_usize()
+            .map(Some)
+            .ok_or_else(|| schema_error(key, "expected non-negative integer")),
+    }
+}
+
 pub(crate) fn parse_ansi_line(line: &str, art: &mut Art) -> Vec<Cell> {
     let mut out = Vec::new();
     let mut iter = line.char_indices().peekable();
@@ -1457,3 +2318,677 @@ fn fg_and_bg() {
     assert_eq!(v[1].text.char, 'B');
     assert_eq!(v[1].color, None);
 }
+
+#[test]
+fn ttyrec_round_trip() {
+    let src =
+        "@3a\ndelay 100 1:250\ncolors yes\n\n@body\nab1_\ncd_2\n\nxy3_\nzw_4\n\nxy3_\nzw_4\n\n";
+    let mut art = Art::from_str(src).unwrap();
+    art.set_frame_delay(2, 700);
+    let back = Art::from_ttyrec(Cursor::new(art.to_ttyrec())).unwrap();
+    assert_eq!(back.frames(), 3);
+    assert_eq!((back.width(), back.height()), (2, 2));
+    for f in 0..3 {
+        assert_eq!(back.get_frame_delay(f), art.get_frame_delay(f));
+        for r in 0..2 {
+            for c in 0..2 {
+                let a = art.get(f, c, r, Cell::default());
+                let b = back.get(f, c, r, Cell::default());
+                assert_eq!(a.text, b.text);
+                assert_eq!(
+                    a.to_pair(&art.header.palette),
+                    b.to_pair(&back.header.palette)
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn ttyrec_delay_options() {
+    let mut rec = Vec::new();
+    for (ts, text) in [(0, "a"), (40, ""), (100, "\rb"), (5000, "\rc")] {
+        let frame = TtyrecFrame {
+            timestamp_ms: ts,
+            text: text.into(),
+        };
+        frame.append_to_vec(&mut rec);
+    }
+    let art = Art::from_ttyrec(Cursor::new(rec.clone())).unwrap();
+    assert_eq!(art.frames(), 4);
+    assert_eq!(art.get_frame_delay(0), 40);
+    assert_eq!(art.get_frame_delay(2), 4900);
+
+    let options = TtyrecOptions {
+        min_delay: 80,
+        max_delay: Some(1000),
+        collapse_idle: true,
+        ..TtyrecOptions::default()
+    };
+    let art = Art::from_ttyrec_with(Cursor::new(rec), &options).unwrap();
+    assert_eq!(art.frames(), 3);
+    assert_eq!(art.get_frame_delay(0), 100);
+    assert_eq!(art.get_frame_delay(1), 1000);
+}
+
+#[test]
+fn asciicast2_round_trip() {
+    let src = "@3a\ntitle Test\ndelay 100 1:250\ncolors yes\n\n@body\nab1_\ncd_2\n\nxy3_\nzw_4\n\nxy3_\nzw_5\n\n";
+    let art = Art::from_str(src).unwrap();
+    let back = Art::from_asciicast2(Cursor::new(art.to_asciicast2())).unwrap();
+    assert_eq!(back.get_title_key(), Some("Test".into()));
+    assert_eq!(back.frames(), 3);
+    assert_eq!((back.width(), back.height()), (2, 2));
+    for f in 0..3 {
+        assert_eq!(back.get_frame_delay(f), art.get_frame_delay(f));
+        for r in 0..2 {
+            for c in 0..2 {
+                let a = art.get(f, c, r, Cell::default());
+                let b = back.get(f, c, r, Cell::default());
+                assert_eq!(a.text, b.text);
+                assert_eq!(
+                    a.to_pair(&art.header.palette),
+                    b.to_pair(&back.header.palette)
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn asciicast2_quantum() {
+    let cast = "{\"version\": 2, \"width\": 3, \"height\": 1, \"idle_time_limit\": 0.5}\n\
+        [0.0, \"o\", \"a\"]\n[0.01, \"o\", \"b\"]\n[0.2, \"o\", \"c\"]\n[2.0, \"o\", \"\\r\"]\n";
+    let art = Art::from_asciicast2(Cursor::new(cast)).unwrap();
+    assert_eq!(art.frames(), 3);
+    assert_eq!(art.get_frame_delay(2), 500);
+    let options = AsciicastOptions {
+        quantum: 100,
+        ..AsciicastOptions::default()
+    };
+    let art = Art::from_asciicast2_with(Cursor::new(cast), &options).unwrap();
+    assert_eq!(art.frames(), 2);
+    assert_eq!(art.get_frame_delay(0), 200);
+    assert_eq!(art.get(0, 1, 0, Cell::default()).text, Char::new_must('b'));
+}
+
+#[test]
+fn asciicast2_time_going_back() {
+    let cast = "{\"version\": 2, \"width\": 2, \"height\": 1}\n\
+        [1.0, \"o\", \"a\"]\n[0.5, \"o\", \"b\"]\n";
+    let art = Art::from_asciicast2(Cursor::new(cast)).unwrap();
+    assert_eq!(art.frames(), 1);
+    assert_eq!(art.get(0, 1, 0, Cell::default()).text, Char::new_must('b'));
+}
+
+#[test]
+fn asciicast3_round_trip() {
+    let src = "@3a\ntitle Test\ndelay 100 1:250\ncolors yes\n\n@body\nab1_\ncd_2\n\nxy3_\nzw_4\n\nxy3_\nzw_5\n\n";
+    let art = Art::from_str(src).unwrap();
+    let cast = art.to_asciicast3();
+    assert!(cast.starts_with("{\"version\": 3, \"term\": {\"cols\": 2, \"rows\": 2}"));
+    let back = Art::from_asciicast3(Cursor::new(cast)).unwrap();
+    assert_eq!(back.get_title_key(), Some("Test".into()));
+    assert_eq!(back.frames(), 3);
+    for f in 0..3 {
+        assert_eq!(back.get_frame_delay(f), art.get_frame_delay(f));
+        assert_eq!(
+            back.frames.frames[f].ansi(&back.header.palette, true),
+            art.frames.frames[f].ansi(&art.header.palette, true)
+        );
+    }
+    assert!(Art::from_asciicast3(Cursor::new(art.to_asciicast2())).is_err());
+}
+
+#[test]
+fn asciicast_one_row_round_trip() {
+    let art = Art::from_str("@3a\ndelay 100 1:300\n\n@body\nab\n\ncd\n\n").unwrap();
+    let v2 = Art::from_asciicast2(Cursor::new(art.to_asciicast2())).unwrap();
+    let v3 = Art::from_asciicast3(Cursor::new(art.to_asciicast3())).unwrap();
+    for back in [v2, v3].iter() {
+        assert_eq!(back.frames(), 2);
+        assert_eq!(back.height(), 1);
+        assert_eq!(text_of(back), "ac");
+        assert_eq!(delays_of(back), vec![100, 300]);
+    }
+}
+
+#[test]
+fn gif_export() {
+    let src = "@3a\ndelay 100 1:250\ncolors yes\nloop no\n\n@body\nA1\n\nB2\n\n";
+    let mut art = Art::from_str(src).unwrap();
+    let font = Font::default();
+    let gif = art.to_gif(&CSSColorMap::default(), &font, &BitmapFont::default());
+    assert_eq!(&gif[..6], b"GIF89a");
+    assert_eq!(&gif[6..10], &[12, 0, 20, 0]);
+    assert!(!gif.windows(11).any(|w| w == b"NETSCAPE2.0"));
+    let gce: Vec<_> = gif
+        .windows(4)
+        .enumerate()
+        .filter(|(_, w)| *w == [0x21, 0xF9, 0x04, 0x04])
+        .map(|(i, _)| u16::from_le_bytes([gif[i + 4], gif[i + 5]]))
+        .collect();
+    assert_eq!(gce, vec![10, 25]);
+    art.set_loop_key(true);
+    let gif = art.to_gif(&CSSColorMap::default(), &font, &BitmapFont::default());
+    assert!(gif.windows(11).any(|w| w == b"NETSCAPE2.0"));
+}
+
+#[test]
+fn png_export() {
+    let src = "@3a\ndelay 100 1:250\ncolors yes\npreview 1\n\n@body\nA1\n\nB2\n\n";
+    let art = Art::from_str(src).unwrap();
+    let font = Font::default();
+    let glyphs = BitmapFont::default();
+    let preview = art.to_png(&CSSColorMap::default(), &font, &glyphs);
+    let frame = art.frames.frames[1].to_png(
+        true,
+        &art.header.palette,
+        &CSSColorMap::default(),
+        &font,
+        &glyphs,
+    );
+    assert_eq!(preview, frame);
+    assert_eq!(&preview[16..24], &[0, 0, 0, 12, 0, 0, 0, 20]);
+    let apng = art.to_apng(&CSSColorMap::default(), &font, &glyphs);
+    let fctl: Vec<_> = apng
+        .windows(4)
+        .enumerate()
+        .filter(|(_, w)| *w == b"fcTL")
+        .map(|(i, _)| u16::from_be_bytes([apng[i + 24], apng[i + 25]]))
+        .collect();
+    assert_eq!(fctl, vec![100, 250]);
+}
+
+#[test]
+fn html_player_export() {
+    let src = "@3a\ntitle <Test>\ncolors yes\npreview 1\n\n@body\n</script>1________\n\nabcdefghi_________\n\n";
+    let art = Art::from_str(src).unwrap();
+    let html = art.to_html_player(&CSSColorMap::default(), &Font::default());
+    assert!(html.contains("<figcaption>&lt;Test&gt;</figcaption>"));
+    assert!(html.contains("\"preview\": 1"));
+    assert!(html.contains("\"delays\": [50, 50]"));
+    assert!(html.contains("<\\/script>"));
+    assert_eq!(html.matches("</script>").count(), 1);
+    assert!(!html.contains("{{"));
+    let empty = Art::new(0, 1, 1, Cell::default());
+    let html = empty.to_html_player(&CSSColorMap::default(), &Font::default());
+    assert!(html.contains("\"frames\": []"));
+}
+
+#[test]
+fn svg_merges_runs_and_frames() {
+    let src = "@3a\ncolors yes\ncol x fg:bright-red bg:blue\n\n@body\nab cdxxx11\n\nab cdxxx11\n\nab cd11111\n\n";
+    let art = Art::from_str(src).unwrap();
+    let svg = art.to_svg_frames(&CSSColorMap::default(), &Font::default());
+    assert!(svg.contains("<tspan x=\"0\" y=\"2\" class=\"f78\">ab</tspan>"));
+    assert!(svg.contains("<tspan x=\"36\" y=\"2\" class=\"f31\">cd</tspan>"));
+    assert!(svg.contains("<rect x=\"0\" y=\"0\" width=\"36\" height=\"20\" class=\"b78\"/>"));
+    assert!(svg.contains("<tspan x=\"0\" y=\"2\" class=\"f31\">ab cd</tspan>"));
+    assert!(svg.contains(".b78 { fill: #000080; }"));
+    assert!(!svg.contains("fill=\"#ff0000\""));
+    assert_eq!(svg.matches("<g ").count(), 3);
+    let frame = art.frames.frames[0].to_svg_frame(
+        true,
+        &art.header.palette,
+        &CSSColorMap::default(),
+        &Font::default(),
+    );
+    assert!(frame.contains("<tspan x=\"0\" y=\"2\" fill=\"#ff0000\">ab</tspan>"));
+    assert!(frame.contains("width=\"36\" height=\"20\" fill=\"#000080\"/>"));
+
+    let options = SvgOptions {
+        skip_unchanged: true,
+        ..SvgOptions::default()
+    };
+    let svg = art.to_svg_frames_with(&CSSColorMap::default(), &Font::default(), &options);
+    assert_eq!(svg.matches("<g ").count(), 2);
+    assert!(svg.contains("dur=\"0.15s\""));
+    assert!(svg.contains("keyTimes=\"0;0.666667;1\""));
+}
+
+#[test]
+fn svg_css_animation() {
+    let src = "@3a\ndelay 100\n\n@body\na\n\nb\n\nc\n\n";
+    let art = Art::from_str(src).unwrap();
+    let options = SvgOptions {
+        animation: crate::SvgAnimation::Css,
+        ..SvgOptions::default()
+    };
+    let svg = art.to_svg_frames_with(&CSSColorMap::default(), &Font::default(), &options);
+    assert!(!svg.contains("<animate"));
+    assert!(svg.contains("<g opacity=\"0\" class=\"a3-f1\">"));
+    assert!(svg.contains("@keyframes a3-f0 { 0% { opacity: 1; } 33.3333% { opacity: 0; } }"));
+    assert!(svg.contains(
+        "@keyframes a3-f1 { 0% { opacity: 0; } 33.3333% { opacity: 1; } 66.6667% { opacity: 0; } }"
+    ));
+    assert!(svg.contains("@keyframes a3-f2 { 0% { opacity: 0; } 66.6667% { opacity: 1; } }"));
+    assert!(svg.contains(".a3-f2 { animation: a3-f2 0.3s steps(1, end) infinite; }"));
+}
+
+#[test]
+fn parse_error_spans() {
+    let src = "@3a\ntitle a\ntitle b\n\n@body\nab\n\n";
+    let err = Art::from_str(src).unwrap_err();
+    let span = err.span().unwrap();
+    assert_eq!((span.line, span.column), (3, Some(1)));
+    assert_eq!(span.block.as_deref(), Some("3a"));
+    assert!(matches!(err.inner(), Error::HeaderKeyDup(_)));
+
+    let src = "@3a\nloop maybe\n\n";
+    let span = Art::from_str(src).unwrap_err().span().cloned().unwrap();
+    assert_eq!((span.line, span.column), (2, Some(6)));
+
+    let src = "@3a\n\n@body\nab\nab\n\nab\nabc\n\n";
+    let err = Art::from_str(src).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "line 8, column 3 (block body, frame 1): width of some art components do not match each other"
+    );
+
+    let src = "@3a\n\n@body\nab\nab\n\nabc\nabc\n\n";
+    let span = Art::from_str(src).unwrap_err().span().cloned().unwrap();
+    assert_eq!((span.line, span.frame), (7, Some(1)));
+
+    let src = "@3a\ncol x fg:nope\n\n";
+    let err = Art::from_str(src).unwrap_err();
+    assert_eq!(err.span().unwrap().column, Some(10));
+    assert!(matches!(err.inner(), Error::ColorParsing(_)));
+
+    let src = "@3a\ncol x  bg:1 fg:X\n\n";
+    assert_eq!(
+        Art::from_str(src).unwrap_err().span().unwrap().column,
+        Some(16)
+    );
+
+    let src = "@3a\n\n@body\nab\n\n\nstray\n";
+    let span = Art::from_str(src).unwrap_err().span().cloned().unwrap();
+    assert_eq!((span.line, span.column, span.block), (7, Some(1), None));
+}
+
+#[test]
+fn io_lines_readers() {
+    use std::io::{BufRead, BufReader};
+    let mut lines = BufReader::new(Cursor::new("@3a\ntitle t\n\nab\ncd\n\n")).lines();
+    let header = Header::read(&mut lines).unwrap();
+    assert_eq!(header.title, Some("t".into()));
+    let frame = Frame::read_text(&mut lines).unwrap();
+    assert_eq!((frame.width(), frame.height()), (2, 2));
+}
+
+#[test]
+fn lenient_parsing() {
+    let src = "@3a\ntitle a\ntitle b\n\n@body\nab\nabc\n\nabc\n\n\n";
+    assert!(Art::from_str(src).is_err());
+    let options = crate::ParseOptions {
+        lenient: true,
+        ..Default::default()
+    };
+    let (art, fixes) = Art::from_reader_with(src.as_bytes(), &options).unwrap();
+    let fixes: Vec<String> = fixes.iter().map(|fix| fix.to_string()).collect();
+    assert_eq!(
+        fixes,
+        vec![
+            "line 3, column 1 (block 3a): duplicate header key 'title', last value is used",
+            "line 7, column 3 (block body, frame 0): truncated row from 3 to 2 cells",
+            "line 9 (block body, frame 1): resized frame from 3x1 to 2x2",
+        ]
+    );
+    assert_eq!(art.header.title.as_deref(), Some("b"));
+    assert_eq!(art.frames.width(), 2);
+    assert_eq!(art.frames.height(), 2);
+
+    let src = "@3a\ncol g fg:black bg:yellow\n\n@body\nabgg\ncdg\n\n\n";
+    assert!(Art::from_str(src).is_err());
+    let (art, fixes) = Art::from_reader_with(src.as_bytes(), &options).unwrap();
+    assert_eq!(
+        fixes[0].to_string(),
+        "line 6, column 4 (block body, frame 0): filled 1 missing color columns"
+    );
+    assert_eq!(art.frames.width(), 2);
+}
+
+#[test]
+fn legacy_writer() {
+    let art = Art::from_str(include_str!("../examples/dna.3a")).unwrap();
+    let legacy = art.to_legacy_string().unwrap();
+    assert!(legacy.starts_with("width 9\nheight 14\n"));
+    assert!(legacy.contains("\ntitle DNA\n"));
+    assert!(legacy.contains("\ncolors fg\n"));
+
+    let read = Art::from_str(&legacy).unwrap();
+    assert_eq!(read.frames(), art.frames());
+    assert_eq!((read.width(), read.height()), (art.width(), art.height()));
+    for f in 0..art.frames() {
+        for r in 0..art.height() {
+            for c in 0..art.width() {
+                let a = art.get(f, c, r, Cell::default());
+                let b = read.get(f, c, r, Cell::default());
+                assert_eq!(a.text, b.text);
+                let fg = a.to_pair(&art.header.palette).fg;
+                assert_eq!(
+                    fg.to_legacy(true),
+                    b.to_pair(&read.header.palette).fg.to_legacy(true)
+                );
+            }
+        }
+    }
+
+    let src = "@3a\n;; slow\ndelay 80\nloop-count 2\n;; kept\n;; unknown\nfoo bar\n\n@body\na\n\n";
+    let art = Art::from_str(src).unwrap();
+    let legacy = art.to_legacy_string().unwrap();
+    assert_eq!(
+        legacy,
+        "width 1\nheight 1\n\tslow\ndelay 80\ncolors none\n\tkept\n\tunknown\nfoo bar\n\na\n\n"
+    );
+    let read = Art::from_str(&legacy).unwrap();
+    assert_eq!(read.get_loop_count(), None);
+    assert_eq!(read.header.delay_comments, vec!["slow"]);
+    assert_eq!(read.header.extra_keys[0].comments, vec!["kept", "unknown"]);
+    let src = "width 1\nheight 1\ncolors none\n\tkept\nloop-count 2\tunknown\n\na\n\n";
+    assert_eq!(Art::from_str(src).unwrap().to_legacy_string().unwrap(), src);
+    let art = Art::from_str("@3a\ndelay 80 1:200\n\n@body\na\n\nb\n\n").unwrap();
+    assert!(matches!(art.to_legacy_string(), Err(Error::LegacyDelay)));
+
+    let mut art = Art::from_str("@3a\ncol x fg:ff8000\n\n@body\naxxx\n\n").unwrap();
+    assert!(matches!(art.to_legacy_string(), Err(Error::LegacyColor(_))));
+    let pair = "fg:bright-red bg:blue".parse().unwrap();
+    art.header.palette.set_color(Char::new_must('x'), pair);
+    assert_eq!(
+        art.to_legacy_string().unwrap(),
+        "width 2\nheight 1\ncolors full\n\naxcc11\n\n"
+    );
+}
+
+#[test]
+fn legacy_backgrounds_and_comments() {
+    let src = "width 2\nheight 1\ntitle T\tnamed\ncolors full\n\tbody follows\n\nab7104\tfirst frame\n\tbetween\ncd7700\n";
+    let art = Art::from_str(src).unwrap();
+    assert_eq!(art.header.title_comments, vec!["named".to_string()]);
+    assert_eq!(
+        art.header.trailing_comments,
+        vec!["body follows", "first frame", "between"]
+    );
+    let options = ParseOptions {
+        lenient: true,
+        ..ParseOptions::default()
+    };
+    let (_, fixes) = Art::from_reader_with(src.as_bytes(), &options).unwrap();
+    let fixes: Vec<String> = fixes.iter().map(|fix| fix.to_string()).collect();
+    assert_eq!(
+        fixes,
+        vec![
+            "line 7, column 7 (frame 0): moved comment 'first frame' to the header",
+            "line 8, column 1 (frame 1): moved comment 'between' to the header",
+        ]
+    );
+    let white_on_black: ColorPair = "fg:white bg:black".parse().unwrap();
+    let blue_on_red: ColorPair = "fg:blue bg:red".parse().unwrap();
+    let pair = |f, c| {
+        art.get(f, c, 0, Cell::default())
+            .to_pair(&art.header.palette)
+    };
+    assert_eq!(pair(0, 0), white_on_black);
+    assert_eq!(pair(0, 1), blue_on_red);
+    assert_eq!(pair(1, 1), white_on_black);
+    assert_eq!(art.header.palette.len(), 2);
+
+    let modern = Art::from_str(&art.to_string()).unwrap();
+    assert_eq!(
+        modern.header.trailing_comments,
+        art.header.trailing_comments
+    );
+    assert_eq!(
+        modern
+            .get(0, 1, 0, Cell::default())
+            .to_pair(&modern.header.palette),
+        blue_on_red
+    );
+    assert_eq!(
+        art.to_legacy_string().unwrap(),
+        "width 2\nheight 1\ntitle T\tnamed\ncolors full\n\tbody follows\n\tfirst frame\n\tbetween\n\nab7104\n\ncd7700\n\n"
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_round_trip() {
+    let art = Art::from_str(include_str!("../examples/dna.3a")).unwrap();
+    let json = serde_json::to_string(&art).unwrap();
+    let read: Art = serde_json::from_str(&json).unwrap();
+    assert_eq!(read.to_string(), art.to_string());
+
+    let frame: Frame = serde_json::from_str(r#"{"text":["ab"],"colors":["x_"]}"#).unwrap();
+    assert_eq!(frame.rows[0][1].color, None);
+    assert_eq!(
+        serde_json::to_string(&frame.rows[0][0]).unwrap(),
+        r#"{"text":"a","color":"x"}"#
+    );
+    let pair: ColorPair = serde_json::from_str(r#"{"fg":"bright-red","bg":null}"#).unwrap();
+    assert_eq!(pair, "fg:bright-red".parse().unwrap());
+
+    assert!(serde_json::from_str::<Char>("\"\\u0001\"").is_err());
+    assert!(serde_json::from_str::<Frame>(r#"{"text":["ab","c"],"colors":["__","_"]}"#).is_err());
+    let frames = r#"{"width":3,"height":1,"frames":[{"text":["ab"],"colors":["__"]}]}"#;
+    assert!(serde_json::from_str::<Frames>(frames).is_err());
+}
+
+#[test]
+fn json_round_trip() {
+    let src = "@3a\ntitle Demo\nauthor alice\n#demo\nloop no\ndelay 40 1:90\ncol 1 fg:green\ncol x fg:ff8000 bg:blue\nfuture-key some value\n\n@attach\nattached data\n\n@notes\nkept\n\n@body\naxb1\n\ncxd_\n\n";
+    let art = Art::from_str(src).unwrap();
+    let read = Art::from_json(&art.to_json()).unwrap();
+    assert_eq!(read.to_string(), art.to_string());
+    assert_eq!(read.to_json(), art.to_json());
+    assert_eq!(read.header.extra_keys[0].line, "future-key some value");
+    assert!(read.contains_tag("demo"));
+
+    let dna = Art::from_str(include_str!("../examples/dna.3a")).unwrap();
+    let read = Art::from_json(&dna.to_json()).unwrap();
+    assert_eq!(read.to_json(), dna.to_json());
+
+    let empty =
+        Art::from_json(r#"{"meta": {"width": 3, "height": 2}, "header": {}, "frames": []}"#)
+            .unwrap();
+    assert_eq!((empty.frames(), empty.width(), empty.height()), (0, 3, 2));
+
+    let ragged = r#"{"header": {}, "frames": [{"text": ["ab"], "colors": ["__"]}, {"text": ["abc"], "colors": ["___"]}]}"#;
+    assert!(matches!(Art::from_json(ragged), Err(Error::WidthMismatch)));
+    let bad = r#"{"header": {"title": 1}, "frames": []}"#;
+    assert!(matches!(Art::from_json(bad), Err(Error::JsonParsing(_))));
+    assert!(JSON_SCHEMA.contains("\"extra-blocks\""));
+}
+
+/// Returns the delays of all frames.
+#[cfg(test)]
+fn delays_of(art: &Art) -> Vec<usize> {
+    (0..art.frames()).map(|f| art.get_frame_delay(f)).collect()
+}
+
+/// Returns the top-left character of every frame.
+#[cfg(test)]
+fn text_of(art: &Art) -> String {
+    (0..art.frames())
+        .map(|f| art.get(f, 0, 0, Cell::default()).text.char)
+        .collect()
+}
+
+#[test]
+fn frame_ops_keep_delays() {
+    let src = "@3a\ndelay 50 1:100 2:200\n\n@body\na\n\nb\n\nc\n\nd\n\n";
+    let art = Art::from_str(src).unwrap();
+
+    let mut edited = art.clone();
+    edited.swap(0, 2);
+    assert_eq!(
+        (text_of(&edited), delays_of(&edited)),
+        ("cbad".into(), vec![200, 100, 50, 50])
+    );
+    let mut edited = art.clone();
+    edited.reverse();
+    assert_eq!(delays_of(&edited), vec![50, 200, 100, 50]);
+    let mut edited = art.clone();
+    edited.rot_forth(1);
+    assert_eq!(
+        (text_of(&edited), delays_of(&edited)),
+        ("dabc".into(), vec![50, 50, 100, 200])
+    );
+    let mut edited = art.clone();
+    edited.rot_back(1);
+    assert_eq!(delays_of(&edited), vec![100, 200, 50, 50]);
+    let mut edited = art.clone();
+    edited.slice(1, 2);
+    assert_eq!(
+        (text_of(&edited), delays_of(&edited)),
+        ("bc".into(), vec![100, 200])
+    );
+    let mut edited = art.clone();
+    edited.remove_frame(1);
+    assert_eq!(delays_of(&edited), vec![50, 200, 50]);
+    let mut edited = art.clone();
+    edited.dup_frame(1);
+    assert_eq!(
+        (text_of(&edited), delays_of(&edited)),
+        ("abbcd".into(), vec![50, 100, 100, 200, 50])
+    );
+
+    let mut art = Art::from_str("@3a\ndelay 40 2:100\n\n@body\na\n\na\n\na\n\nb\n\nb\n\n").unwrap();
+    art.dedup();
+    assert_eq!(
+        (text_of(&art), delays_of(&art)),
+        ("ab".into(), vec![180, 80])
+    );
+    let mut art = Art::from_str("@3a\n\n@body\na\n\na\n\nb\n\n").unwrap();
+    art.dedup();
+    assert_eq!(delays_of(&art), vec![100, 50]);
+    let mut art = Art::from_str("@3a\n\n@body\na\n\nb\n\n").unwrap();
+    art.reverse();
+    assert!(art.header.delay.is_none());
+}
+
+#[test]
+fn timeline_lookup() {
+    let mut art = Art::from_str("@3a\ndelay 100 1:300\n\n@body\na\n\nb\n\nc\n\n").unwrap();
+    let ms = Duration::from_millis;
+    assert_eq!(art.timeline().duration(), ms(500));
+    assert_eq!(art.start_time(2), Some(ms(400)));
+    assert_eq!(art.frame_at(ms(399)), Some(1));
+    assert_eq!(art.frame_at(ms(650)), Some(1));
+    art.set_loop_key(false);
+    assert_eq!(art.frame_at(ms(650)), Some(2));
+    assert_eq!(Art::new(0, 1, 1, Cell::default()).frame_at(ms(0)), None);
+}
+
+#[test]
+fn resample_and_coalesce() {
+    let src = "@3a\ndelay 110 1:20 2:300\npreview 2\n\n@body\na\n\nb\n\nc\n\n";
+    let art = Art::from_str(src).unwrap();
+
+    let resampled = art.resample(20.0).unwrap();
+    assert_eq!(text_of(&resampled), "aaacccccc");
+    assert!((0..resampled.frames()).all(|f| resampled.get_frame_delay(f) == 50));
+    assert!(resampled.header.delay.is_none());
+    assert_eq!(resampled.get_preview_key(), Some(3));
+    assert_eq!(resampled.duration(), 0.45);
+
+    let resampled = art.resample(100.0).unwrap();
+    assert_eq!(resampled.frames(), 43);
+    assert_eq!(resampled.get_global_delay(), 10);
+    let coalesced = resampled.coalesce();
+    assert_eq!(text_of(&coalesced), "abc");
+    assert_eq!(coalesced.timeline(), art.timeline());
+
+    assert_eq!(
+        Art::new(0, 1, 1, Cell::default())
+            .resample(30.0)
+            .unwrap()
+            .frames(),
+        0
+    );
+    for fps in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+        assert!(matches!(art.resample(fps), Err(Error::FrameRate(_))));
+    }
+    let mut art = art;
+    art.header.preview = Some(5);
+    assert_eq!(art.resample(20.0).unwrap().header.preview, None);
+}
+
+#[test]
+fn timing_transforms() {
+    let src = "@3a\ndelay 100 2:300\n\n@body\na\n\nb\n\nc\n\nd\n\n";
+    let art = Art::from_str(src).unwrap();
+
+    let mut edited = art.clone();
+    edited.scale_delays(0.5).unwrap();
+    assert_eq!(delays_of(&edited), vec![50, 50, 150, 50]);
+    assert_eq!(edited.header.delay.as_ref().unwrap().per_frame.len(), 1);
+    let mut edited = art.clone();
+    edited.clamp_delays(120, Some(200));
+    assert_eq!(delays_of(&edited), vec![120, 120, 200, 120]);
+
+    let mut edited = art.clone();
+    edited.ping_pong();
+    assert_eq!(text_of(&edited), "abcdcb");
+    assert_eq!(delays_of(&edited), vec![100, 100, 300, 100, 300, 100]);
+    let mut edited = art.clone();
+    edited.repeat(2);
+    assert_eq!(text_of(&edited), "abcdabcd");
+    assert_eq!(edited.get_frame_delay(6), 300);
+
+    let mut art = Art::from_str("@3a\n\n@body\na\n\nb\n\n").unwrap();
+    art.ping_pong();
+    art.repeat(0);
+    assert_eq!(art.frames(), 2);
+    assert!(art.header.delay.is_none());
+    art.scale_delays(2.0).unwrap();
+    assert_eq!(art.to_string(), "@3a\ndelay 100\n\n@body\na\n\nb\n\n");
+    for factor in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+        assert!(matches!(
+            art.scale_delays(factor),
+            Err(Error::DelayScale(_))
+        ));
+    }
+    assert_eq!(delays_of(&art), vec![100, 100]);
+}
+
+#[test]
+fn loop_count() {
+    let src = "@3a\nloop-count 3\n\n@body\na\n\nb\n\n";
+    let mut art = Art::from_str(src).unwrap();
+    assert_eq!(art.to_string(), src);
+    assert!(art.get_loop_key());
+    assert_eq!(art.get_loop_count(), Some(3));
+    assert_eq!(Art::from_json(&art.to_json()).unwrap().to_string(), src);
+
+    let events = art.asciicast_events();
+    assert_eq!(events.len(), 8);
+    assert_eq!(events[3].0, 100);
+    assert_eq!(events[7].0, 300);
+    let svg = art.to_svg_frames(&CSSColorMap::default(), &Font::default());
+    assert!(svg.contains("repeatCount=\"3\" fill=\"freeze\""));
+    assert!(svg.contains("values=\"0;1;1\""));
+    let options = SvgOptions {
+        plays: Some(2),
+        ..SvgOptions::default()
+    };
+    let svg = art.to_svg_frames_with(&CSSColorMap::default(), &Font::default(), &options);
+    assert!(svg.contains("repeatCount=\"2\""));
+    let ms = Duration::from_millis;
+    assert_eq!(art.frame_at(ms(250)), Some(1));
+    assert_eq!(art.frame_at(ms(210)), Some(0));
+    assert_eq!(art.frame_at(ms(320)), Some(1));
+
+    art.set_loop_count(Some(1));
+    assert_eq!(art.to_string(), "@3a\nloop no\n\n@body\na\n\nb\n\n");
+    assert_eq!(art.asciicast_events().len(), 4);
+    art.set_loop_count(Some(5));
+    assert_eq!(art.get_loop_count(), Some(5));
+    art.set_loop_key(true);
+    assert_eq!(art.get_loop_count(), None);
+    assert_eq!(art.to_string(), "@3a\n\n@body\na\n\nb\n\n");
+
+    let err = Art::from_str("@3a\nloop-count x\n\n@body\na\n\n").unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("failed to parse loop count value 'x'"));
+}
@@ -0,0 +1,179 @@
+use std::io::{BufRead, BufReader, Read};
+
+use crate::error::{Error, Result};
+use crate::json::{self, JsonValue};
+
+/// Options for importing asciicast recordings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsciicastOptions {
+    /// Output events starting less than `quantum` milliseconds after the
+    /// first event of the current frame are merged into that frame.
+    /// With 0 only events sharing a timestamp are merged.
+    pub quantum: usize,
+    /// Lower bound for frame delays in milliseconds (at least 1).
+    pub min_delay: usize,
+    /// Upper bound for frame delays in milliseconds.
+    /// If not set, `idle_time_limit` from the cast header is used.
+    pub max_delay: Option<usize>,
+    /// Merges consecutive frames with identical screen content,
+    /// summing their delays.
+    pub collapse_idle: bool,
+}
+
+impl Default for AsciicastOptions {
+    /// One frame per event, original timing.
+    fn default() -> Self {
+        Self {
+            quantum: 0,
+            min_delay: 1,
+            max_delay: None,
+            collapse_idle: false,
+        }
+    }
+}
+
+/// Parsed asciicast header.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct CastHeader {
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+    pub(crate) title: Option<String>,
+    /// Idle time limit in milliseconds.
+    pub(crate) idle_time_limit: Option<usize>,
+    /// Total duration in milliseconds.
+    pub(crate) duration: Option<usize>,
+}
+
+/// A single asciicast event with an absolute timestamp.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CastEvent {
+    pub(crate) time_ms: usize,
+    pub(crate) code: String,
+    pub(crate) data: String,
+}
+
+fn secs_to_ms(secs: f64) -> usize {
+    if secs > 0.0 {
+        (secs * 1000.0).round() as usize
+    } else {
+        0
+    }
+}
+
+fn header_error(msg: &str) -> Error {
+    Error::AsciicastParsing(format!("header: {}", msg))
+}
+
+fn parse_header_v2(value: &JsonValue) -> Result<CastHeader> {
+    Ok(CastHeader {
+        width: value
+            .get("width")
+            .and_then(JsonValue::as_usize)
+            .ok_or_else(|| header_error("missing width"))?,
+        height: value
+            .get("height")
+            .and_then(JsonValue::as_usize)
+            .ok_or_else(|| header_error("missing height"))?,
+        title: value
+            .get("title")
+            .and_then(JsonValue::as_str)
+            .map(String::from),
+        idle_time_limit: value
+            .get("idle_time_limit")
+            .and_then(JsonValue::as_f64)
+            .map(secs_to_ms),
+        duration: value
+            .get("duration")
+            .and_then(JsonValue::as_f64)
+            .map(secs_to_ms),
+    })
+}
+
+fn parse_event(line: &str) -> Result<(f64, String, String)> {
+    let err = || Error::AsciicastParsing(format!("invalid event: {}", line));
+    let value = json::parse(line)?;
+    let items = value.as_array().ok_or_else(err)?;
+    if items.len() < 3 {
+        return Err(err());
+    }
+    let time = items[0].as_f64().ok_or_else(err)?;
+    let code = items[1].as_str().ok_or_else(err)?;
+    let data = items[2].as_str().ok_or_else(err)?;
+    Ok((time, code.into(), data.into()))
+}
+
+fn parse_header_v3(value: &JsonValue) -> Result<CastHeader> {
+    let term = value
+        .get("term")
+        .ok_or_else(|| header_error("missing term"))?;
+    Ok(CastHeader {
+        width: term
+            .get("cols")
+            .and_then(JsonValue::as_usize)
+            .ok_or_else(|| header_error("missing term.cols"))?,
+        height: term
+            .get("rows")
+            .and_then(JsonValue::as_usize)
+            .ok_or_else(|| header_error("missing term.rows"))?,
+        title: value
+            .get("title")
+            .and_then(JsonValue::as_str)
+            .map(String::from),
+        idle_time_limit: value
+            .get("idle_time_limit")
+            .and_then(JsonValue::as_f64)
+            .map(secs_to_ms),
+        duration: None,
+    })
+}
+
+/// Reads an asciicast v2 recording: an NDJSON header line followed by
+/// `[time, code, data]` event lines with absolute timestamps in seconds.
+pub(crate) fn read_v2<R: Read>(reader: R) -> Result<(CastHeader, Vec<CastEvent>)> {
+    read(reader, 2)
+}
+
+/// Reads an asciicast v3 recording: an NDJSON header line followed by
+/// `[interval, code, data]` event lines, where the interval is the time
+/// in seconds since the previous event. Lines starting with `#` are comments.
+pub(crate) fn read_v3<R: Read>(reader: R) -> Result<(CastHeader, Vec<CastEvent>)> {
+    read(reader, 3)
+}
+
+fn read<R: Read>(reader: R, version: usize) -> Result<(CastHeader, Vec<CastEvent>)> {
+    let mut header: Option<CastHeader> = None;
+    let mut events = Vec::new();
+    let mut clock = 0.0;
+    for line in BufReader::new(reader).lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || (version > 2 && line.starts_with('#')) {
+            continue;
+        }
+        if header.is_none() {
+            let value = json::parse(line)?;
+            if value.get("version").and_then(JsonValue::as_usize) != Some(version) {
+                return Err(header_error(&format!("version {} expected", version)));
+            }
+            header = Some(match version {
+                2 => parse_header_v2(&value)?,
+                _ => parse_header_v3(&value)?,
+            });
+            continue;
+        }
+        let (time, code, data) = parse_event(line)?;
+        clock = match version {
+            2 => time,
+            _ => clock + time,
+        };
+        events.push(CastEvent {
+            time_ms: secs_to_ms(clock),
+            code,
+            data,
+        });
+    }
+    match header {
+        Some(header) => Ok((header, events)),
+        None => Err(Error::UnexpectedEof),
+    }
+}
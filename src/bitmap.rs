@@ -0,0 +1,669 @@
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fs;
+use std::path::Path;
+
+use crate::chars::Char;
+use crate::error::{Error, Result};
+
+const PSF1_MAGIC: [u8; 2] = [0x36, 0x04];
+const PSF2_MAGIC: [u8; 4] = [0x72, 0xb5, 0x4a, 0x86];
+
+/// Largest glyph width or height accepted from font files, in pixels.
+const MAX_GLYPH_SIZE: usize = 256;
+
+/// A monochrome glyph bitmap.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Glyph {
+    width: usize,
+    height: usize,
+    pixels: Vec<bool>,
+}
+
+impl Glyph {
+    /// Creates an empty glyph of the given size.
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![false; width * height],
+        }
+    }
+
+    /// Returns the glyph width in pixels.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Returns the glyph height in pixels.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns whether the pixel is set; pixels outside the glyph are unset.
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height && self.pixels[y * self.width + x]
+    }
+
+    /// Sets or clears the pixel; pixels outside the glyph are ignored.
+    pub fn set(&mut self, x: usize, y: usize, on: bool) {
+        if x < self.width && y < self.height {
+            self.pixels[y * self.width + x] = on;
+        }
+    }
+}
+
+/// A bitmap font with fixed size glyphs, used by raster exports.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BitmapFont {
+    width: usize,
+    height: usize,
+    glyphs: HashMap<char, Glyph>,
+}
+
+impl BitmapFont {
+    /// Creates an empty font with the given glyph size.
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            glyphs: HashMap::new(),
+        }
+    }
+
+    /// Returns the compiled-in 8x16 font covering printable ASCII,
+    /// box-drawing (U+2500..U+257F) and block elements (U+2580..U+259F).
+    pub fn builtin() -> Self {
+        let mut font = Self::new(8, 16);
+        for (i, rows) in BUILTIN_ASCII.iter().enumerate() {
+            let mut glyph = Glyph::new(8, 16);
+            for (y, row) in rows.iter().enumerate() {
+                for x in 0..8 {
+                    let on = row & (1 << x) != 0;
+                    glyph.set(x, y * 2, on);
+                    glyph.set(x, y * 2 + 1, on);
+                }
+            }
+            font.glyphs.insert((0x20 + i as u8) as char, glyph);
+        }
+        font.add_drawn_glyphs();
+        font
+    }
+
+    /// Loads a font from a PSF1, PSF2 or BDF file, detected by content.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let data = fs::read(path)?;
+        if data.starts_with(&PSF1_MAGIC) || data.starts_with(&PSF2_MAGIC) {
+            Self::from_psf(&data)
+        } else {
+            Self::from_bdf(std::str::from_utf8(&data).map_err(|_| Error::NotUtf8)?)
+        }
+    }
+
+    /// Parses a Linux console PSF1 or PSF2 font.
+    /// Glyphs are mapped through the unicode table if the font has one,
+    /// otherwise glyph indices are taken as code points.
+    /// Missing box-drawing and block glyphs are drawn at the font size.
+    pub fn from_psf(data: &[u8]) -> Result<Self> {
+        let err = |msg: &str| Error::FontParsing(format!("psf: {}", msg));
+        let (width, height, count, glyph_size, offset, unicode) = if data.starts_with(&PSF1_MAGIC) {
+            if data.len() < 4 {
+                return Err(err("truncated header"));
+            }
+            let count = if data[2] & 0x01 != 0 { 512 } else { 256 };
+            let height = data[3] as usize;
+            (8, height, count, height, 4, data[2] & 0x06 != 0)
+        } else if data.starts_with(&PSF2_MAGIC) {
+            if data.len() < 32 {
+                return Err(err("truncated header"));
+            }
+            let field = |i: usize| u32::from_le_bytes(data[i * 4..i * 4 + 4].try_into().unwrap());
+            let (flags, count, glyph_size) = (field(3), field(4) as usize, field(5) as usize);
+            let (height, width) = (field(6) as usize, field(7) as usize);
+            if width > MAX_GLYPH_SIZE || height > MAX_GLYPH_SIZE {
+                return Err(err("glyph too large"));
+            }
+            if glyph_size < height * ((width + 7) / 8) {
+                return Err(err("glyph size too small"));
+            }
+            (
+                width,
+                height,
+                count,
+                glyph_size,
+                field(2) as usize,
+                flags & 0x01 != 0,
+            )
+        } else {
+            return Err(err("bad magic"));
+        };
+        let table = count
+            .checked_mul(glyph_size)
+            .and_then(|size| size.checked_add(offset));
+        let table = match table {
+            Some(table) if table <= data.len() => table,
+            _ => return Err(err("truncated glyph data")),
+        };
+        let row_bytes = (width + 7) / 8;
+        let mut glyphs = Vec::with_capacity(count);
+        for g in 0..count {
+            let bytes = &data[offset + g * glyph_size..offset + (g + 1) * glyph_size];
+            let mut glyph = Glyph::new(width, height);
+            for y in 0..height {
+                for x in 0..width {
+                    let byte = bytes[y * row_bytes + x / 8];
+                    glyph.set(x, y, byte & (0x80 >> (x % 8)) != 0);
+                }
+            }
+            glyphs.push(glyph);
+        }
+        let mut font = Self::new(width, height);
+        if !unicode {
+            for (g, glyph) in glyphs.into_iter().enumerate() {
+                if let Some(ch) = std::char::from_u32(g as u32) {
+                    font.glyphs.insert(ch, glyph);
+                }
+            }
+        } else if data.starts_with(&PSF1_MAGIC) {
+            let mut entries = data[table..]
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]));
+            for glyph in &glyphs {
+                let mut sequence = false;
+                for code in entries.by_ref() {
+                    match code {
+                        0xFFFF => break,
+                        0xFFFE => sequence = true,
+                        _ if sequence => {}
+                        _ => {
+                            if let Some(ch) = std::char::from_u32(code as u32) {
+                                font.glyphs.insert(ch, glyph.clone());
+                            }
+                        }
+                    }
+                }
+            }
+        } else {
+            let mut rest = &data[table..];
+            for glyph in &glyphs {
+                let end = rest.iter().position(|b| *b == 0xFF).unwrap_or(rest.len());
+                let entry = &rest[..end];
+                // Entries after 0xFE are multi-character sequences.
+                let single = &entry[..entry.iter().position(|b| *b == 0xFE).unwrap_or(end)];
+                for ch in String::from_utf8_lossy(single).chars() {
+                    if ch != char::REPLACEMENT_CHARACTER {
+                        font.glyphs.insert(ch, glyph.clone());
+                    }
+                }
+                rest = &rest[(end + 1).min(rest.len())..];
+            }
+        }
+        font.add_drawn_glyphs();
+        Ok(font)
+    }
+
+    /// Parses an X11 BDF font.
+    /// Glyphs are placed into the font bounding box on a common baseline.
+    /// Missing box-drawing and block glyphs are drawn at the font size.
+    pub fn from_bdf(text: &str) -> Result<Self> {
+        let err = |msg: &str| Error::FontParsing(format!("bdf: {}", msg));
+        let numbers = |args: &[&str]| -> Result<Vec<i64>> {
+            args.iter()
+                .map(|a| {
+                    a.parse::<i64>()
+                        .map_err(|_| err(&format!("bad number {}", a)))
+                })
+                .collect()
+        };
+        let mut bbox: Option<Vec<i64>> = None;
+        let mut ascent: Option<i64> = None;
+        let mut descent: Option<i64> = None;
+        let mut font: Option<Self> = None;
+        let mut encoding: i64 = -1;
+        let mut bbx: Vec<i64> = vec![0; 4];
+        let mut bitmap: Option<Vec<u64>> = None;
+        for line in text.lines() {
+            let mut words = line.split_whitespace();
+            let keyword = words.next().unwrap_or("");
+            let args: Vec<&str> = words.collect();
+            if let Some(rows) = bitmap.as_mut() {
+                if keyword != "ENDCHAR" {
+                    let row = u64::from_str_radix(keyword, 16)
+                        .map_err(|_| err(&format!("bad bitmap row {}", keyword)))?;
+                    rows.push(row);
+                    continue;
+                }
+            }
+            match keyword {
+                "FONTBOUNDINGBOX" => bbox = Some(numbers(&args)?),
+                "FONT_ASCENT" => {
+                    let value = numbers(&args)?.first().copied();
+                    ascent = Some(value.ok_or_else(|| err("bad FONT_ASCENT"))?);
+                }
+                "FONT_DESCENT" => {
+                    let value = numbers(&args)?.first().copied();
+                    descent = Some(value.ok_or_else(|| err("bad FONT_DESCENT"))?);
+                }
+                "STARTCHAR" => {
+                    encoding = -1;
+                    bbx = vec![0; 4];
+                }
+                "ENCODING" => encoding = *numbers(&args)?.first().unwrap_or(&-1),
+                "BBX" => bbx = numbers(&args)?,
+                "BITMAP" => bitmap = Some(Vec::new()),
+                "ENDCHAR" => {
+                    let rows = bitmap.take().unwrap_or_default();
+                    let fb = bbox
+                        .as_ref()
+                        .ok_or_else(|| err("missing FONTBOUNDINGBOX"))?;
+                    if fb.len() < 4 || bbx.len() < 4 {
+                        return Err(err("bad bounding box"));
+                    }
+                    let size = 0..=MAX_GLYPH_SIZE as i64;
+                    if !size.contains(&fb[0]) || !size.contains(&fb[1]) {
+                        return Err(err("bad FONTBOUNDINGBOX"));
+                    }
+                    // Bitmap rows are read into u64.
+                    if !(0..=64).contains(&bbx[0]) || !size.contains(&bbx[1]) {
+                        return Err(err("bad BBX"));
+                    }
+                    let overflow = || err("bounding box out of range");
+                    let base = match ascent {
+                        Some(ascent) => ascent,
+                        None => fb[1].checked_add(fb[3]).ok_or_else(overflow)?,
+                    };
+                    let height = match (ascent, descent) {
+                        (Some(a), Some(d)) => a.checked_add(d).ok_or_else(overflow)?,
+                        _ => fb[1],
+                    };
+                    if height > MAX_GLYPH_SIZE as i64 {
+                        return Err(err("bad FONT_ASCENT or FONT_DESCENT"));
+                    }
+                    let font = font
+                        .get_or_insert_with(|| Self::new(fb[0] as usize, height.max(0) as usize));
+                    let ch = match std::char::from_u32(encoding.max(0) as u32) {
+                        Some(ch) if encoding >= 0 => ch,
+                        _ => continue,
+                    };
+                    let row_bits = ((bbx[0] + 7) / 8 * 8) as u32;
+                    let left = bbx[2].checked_sub(fb[2]).ok_or_else(overflow)?;
+                    let top = bbx[3]
+                        .checked_add(bbx[1])
+                        .and_then(|bottom| base.checked_sub(bottom))
+                        .ok_or_else(overflow)?;
+                    let mut glyph = Glyph::new(font.width, font.height);
+                    for (y, row) in rows.iter().enumerate() {
+                        for x in 0..bbx[0] {
+                            if row_bits > x as u32 && row >> (row_bits - 1 - x as u32) & 1 != 0 {
+                                let gx = left.saturating_add(x);
+                                let gy = top.saturating_add(y as i64);
+                                if gx >= 0 && gy >= 0 {
+                                    glyph.set(gx as usize, gy as usize, true);
+                                }
+                            }
+                        }
+                    }
+                    font.glyphs.insert(ch, glyph);
+                }
+                _ => {}
+            }
+        }
+        let mut font = font.ok_or_else(|| err("no glyphs"))?;
+        font.add_drawn_glyphs();
+        Ok(font)
+    }
+
+    /// Draws box-drawing and block element glyphs missing from the font.
+    fn add_drawn_glyphs(&mut self) {
+        for code in 0x2500..=0x259F {
+            let ch = std::char::from_u32(code).unwrap();
+            if !self.glyphs.contains_key(&ch) {
+                if let Some(glyph) = drawn_glyph(ch, self.width, self.height) {
+                    self.glyphs.insert(ch, glyph);
+                }
+            }
+        }
+    }
+
+    /// Returns the glyph width in pixels.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Returns the glyph height in pixels.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns the glyph for a character, if the font has one.
+    pub fn glyph(&self, ch: Char) -> Option<&Glyph> {
+        self.glyphs.get(&ch.char)
+    }
+
+    /// Sets the glyph for a character.
+    /// Glyphs of a different size are drawn from the top left corner of the cell.
+    pub fn set_glyph(&mut self, ch: Char, glyph: Glyph) {
+        self.glyphs.insert(ch.char, glyph);
+    }
+}
+
+impl Default for BitmapFont {
+    /// Returns the compiled-in font.
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+/// Line weights of box-drawing characters U+2500..U+257F as
+/// up, right, down, left arms: 0 none, 1 light, 2 heavy, 3 double.
+/// Dashed lines are drawn solid; `/` marks diagonals.
+const BOX_ARMS: [&str; 128] = [
+    "0101", "0202", "1010", "2020", "0101", "0202", "1010", "2020", // 2500
+    "0101", "0202", "1010", "2020", "0110", "0210", "0120", "0220", // 2508
+    "0011", "0012", "0021", "0022", "1100", "1200", "2100", "2200", // 2510
+    "1001", "1002", "2001", "2002", "1110", "1210", "2110", "1120", // 2518
+    "2120", "2210", "1220", "2220", "1011", "1012", "2011", "1021", // 2520
+    "2021", "2012", "1022", "2022", "0111", "0112", "0211", "0212", // 2528
+    "0121", "0122", "0221", "0222", "1101", "1102", "1201", "1202", // 2530
+    "2101", "2102", "2201", "2202", "1111", "1112", "1211", "1212", // 2538
+    "2111", "1121", "2121", "2112", "2211", "1122", "1221", "2212", // 2540
+    "1222", "2122", "2221", "2222", "0101", "0202", "1010", "2020", // 2548
+    "0303", "3030", "0310", "0130", "0330", "0013", "0031", "0033", // 2550
+    "1300", "3100", "3300", "1003", "3001", "3003", "1310", "3130", // 2558
+    "3330", "1013", "3031", "3033", "0313", "0131", "0333", "1303", // 2560
+    "3101", "3303", "1313", "3131", "3333", "0110", "0011", "1001", // 2568
+    "1100", "/", "/", "/", "0001", "1000", "0100", "0010", // 2570
+    "0002", "2000", "0200", "0020", "0201", "1020", "0102", "2010", // 2578
+];
+
+/// Draws box-drawing and block element glyphs at any size.
+fn drawn_glyph(ch: char, width: usize, height: usize) -> Option<Glyph> {
+    let mut glyph = Glyph::new(width, height);
+    let (w, h) = (width as i64, height as i64);
+    let mut fill = |x0: i64, y0: i64, x1: i64, y1: i64| {
+        for y in y0.max(0)..y1.min(h) {
+            for x in x0.max(0)..x1.min(w) {
+                glyph.set(x as usize, y as usize, true);
+            }
+        }
+    };
+    let code = ch as u32;
+    match code {
+        0x2500..=0x257F => {
+            let (cx, cy) = ((w - 1) / 2, (h - 1) / 2);
+            let arms = BOX_ARMS[(code - 0x2500) as usize];
+            if arms == "/" {
+                for y in 0..h {
+                    let x = y * w / h;
+                    if code != 0x2571 {
+                        fill(x, y, x + 1, y + 1);
+                    }
+                    if code != 0x2572 {
+                        fill(w - 1 - x, y, w - x, y + 1);
+                    }
+                }
+                return Some(glyph);
+            }
+            let offsets = |weight: u8| -> &'static [i64] {
+                match weight {
+                    b'1' => &[0],
+                    b'2' => &[-1, 0, 1],
+                    b'3' => &[-1, 1],
+                    _ => &[],
+                }
+            };
+            let arms = arms.as_bytes();
+            // Arms reach over the center as far as the crossing lines are thick.
+            let thick = |a: u8, b: u8| if a > b'1' || b > b'1' { 1 } else { 0 };
+            let (ev, eh) = (thick(arms[0], arms[2]), thick(arms[1], arms[3]));
+            for d in offsets(arms[0]) {
+                fill(cx + d, 0, cx + d + 1, cy + eh + 1);
+            }
+            for d in offsets(arms[1]) {
+                fill(cx - ev, cy + d, w, cy + d + 1);
+            }
+            for d in offsets(arms[2]) {
+                fill(cx + d, cy - eh, cx + d + 1, h);
+            }
+            for d in offsets(arms[3]) {
+                fill(0, cy + d, cx + ev + 1, cy + d + 1);
+            }
+        }
+        0x2580 => fill(0, 0, w, h / 2),
+        0x2581..=0x2588 => fill(0, h - h * (code - 0x2580) as i64 / 8, w, h),
+        0x2589..=0x258F => fill(0, 0, w * (0x2590 - code) as i64 / 8, h),
+        0x2590 => fill(w / 2, 0, w, h),
+        0x2591..=0x2593 => {
+            for y in 0..h {
+                for x in 0..w {
+                    let on = match code {
+                        0x2591 => x % 2 == 0 && y % 2 == 0,
+                        0x2592 => (x + y) % 2 == 0,
+                        _ => x % 2 == 0 || y % 2 == 0,
+                    };
+                    if on {
+                        fill(x, y, x + 1, y + 1);
+                    }
+                }
+            }
+        }
+        0x2594 => fill(0, 0, w, (h / 8).max(1)),
+        0x2595 => fill(w - (w / 8).max(1), 0, w, h),
+        0x2596..=0x259F => {
+            // Quadrants as upper left, upper right, lower left, lower right.
+            let quadrants = [
+                "0010", "0001", "1000", "1011", "1001", "1110", "1101", "0100", "0110", "0111",
+            ][(code - 0x2596) as usize];
+            let (mx, my) = (w / 2, h / 2);
+            for (q, on) in quadrants.bytes().enumerate() {
+                if on == b'1' {
+                    let (x0, x1) = if q % 2 == 0 { (0, mx) } else { (mx, w) };
+                    let (y0, y1) = if q < 2 { (0, my) } else { (my, h) };
+                    fill(x0, y0, x1, y1);
+                }
+            }
+        }
+        _ => return None,
+    }
+    Some(glyph)
+}
+
+/// Public domain 8x8 glyphs for 0x20..=0x7e, one byte per row,
+/// least significant bit is the leftmost pixel.
+const BUILTIN_ASCII: [[u8; 8]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x18, 0x3C, 0x3C, 0x18, 0x18, 0x00, 0x18, 0x00], // '!'
+    [0x36, 0x36, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '"'
+    [0x36, 0x36, 0x7F, 0x36, 0x7F, 0x36, 0x36, 0x00], // '#'
+    [0x0C, 0x3E, 0x03, 0x1E, 0x30, 0x1F, 0x0C, 0x00], // '$'
+    [0x00, 0x63, 0x33, 0x18, 0x0C, 0x66, 0x63, 0x00], // '%'
+    [0x1C, 0x36, 0x1C, 0x6E, 0x3B, 0x33, 0x6E, 0x00], // '&'
+    [0x06, 0x06, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00], // '''
+    [0x18, 0x0C, 0x06, 0x06, 0x06, 0x0C, 0x18, 0x00], // '('
+    [0x06, 0x0C, 0x18, 0x18, 0x18, 0x0C, 0x06, 0x00], // ')'
+    [0x00, 0x66, 0x3C, 0xFF, 0x3C, 0x66, 0x00, 0x00], // '*'
+    [0x00, 0x0C, 0x0C, 0x3F, 0x0C, 0x0C, 0x00, 0x00], // '+'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C, 0x06], // ','
+    [0x00, 0x00, 0x00, 0x3F, 0x00, 0x00, 0x00, 0x00], // '-'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C, 0x00], // '.'
+    [0x60, 0x30, 0x18, 0x0C, 0x06, 0x03, 0x01, 0x00], // '/'
+    [0x3E, 0x63, 0x73, 0x7B, 0x6F, 0x67, 0x3E, 0x00], // '0'
+    [0x0C, 0x0E, 0x0C, 0x0C, 0x0C, 0x0C, 0x3F, 0x00], // '1'
+    [0x1E, 0x33, 0x30, 0x1C, 0x06, 0x33, 0x3F, 0x00], // '2'
+    [0x1E, 0x33, 0x30, 0x1C, 0x30, 0x33, 0x1E, 0x00], // '3'
+    [0x38, 0x3C, 0x36, 0x33, 0x7F, 0x30, 0x78, 0x00], // '4'
+    [0x3F, 0x03, 0x1F, 0x30, 0x30, 0x33, 0x1E, 0x00], // '5'
+    [0x1C, 0x06, 0x03, 0x1F, 0x33, 0x33, 0x1E, 0x00], // '6'
+    [0x3F, 0x33, 0x30, 0x18, 0x0C, 0x0C, 0x0C, 0x00], // '7'
+    [0x1E, 0x33, 0x33, 0x1E, 0x33, 0x33, 0x1E, 0x00], // '8'
+    [0x1E, 0x33, 0x33, 0x3E, 0x30, 0x18, 0x0E, 0x00], // '9'
+    [0x00, 0x0C, 0x0C, 0x00, 0x00, 0x0C, 0x0C, 0x00], // ':'
+    [0x00, 0x0C, 0x0C, 0x00, 0x00, 0x0C, 0x0C, 0x06], // ';'
+    [0x18, 0x0C, 0x06, 0x03, 0x06, 0x0C, 0x18, 0x00], // '<'
+    [0x00, 0x00, 0x3F, 0x00, 0x00, 0x3F, 0x00, 0x00], // '='
+    [0x06, 0x0C, 0x18, 0x30, 0x18, 0x0C, 0x06, 0x00], // '>'
+    [0x1E, 0x33, 0x30, 0x18, 0x0C, 0x00, 0x0C, 0x00], // '?'
+    [0x3E, 0x63, 0x7B, 0x7B, 0x7B, 0x03, 0x1E, 0x00], // '@'
+    [0x0C, 0x1E, 0x33, 0x33, 0x3F, 0x33, 0x33, 0x00], // 'A'
+    [0x3F, 0x66, 0x66, 0x3E, 0x66, 0x66, 0x3F, 0x00], // 'B'
+    [0x3C, 0x66, 0x03, 0x03, 0x03, 0x66, 0x3C, 0x00], // 'C'
+    [0x1F, 0x36, 0x66, 0x66, 0x66, 0x36, 0x1F, 0x00], // 'D'
+    [0x7F, 0x46, 0x16, 0x1E, 0x16, 0x46, 0x7F, 0x00], // 'E'
+    [0x7F, 0x46, 0x16, 0x1E, 0x16, 0x06, 0x0F, 0x00], // 'F'
+    [0x3C, 0x66, 0x03, 0x03, 0x73, 0x66, 0x7C, 0x00], // 'G'
+    [0x33, 0x33, 0x33, 0x3F, 0x33, 0x33, 0x33, 0x00], // 'H'
+    [0x1E, 0x0C, 0x0C, 0x0C, 0x0C, 0x0C, 0x1E, 0x00], // 'I'
+    [0x78, 0x30, 0x30, 0x30, 0x33, 0x33, 0x1E, 0x00], // 'J'
+    [0x67, 0x66, 0x36, 0x1E, 0x36, 0x66, 0x67, 0x00], // 'K'
+    [0x0F, 0x06, 0x06, 0x06, 0x46, 0x66, 0x7F, 0x00], // 'L'
+    [0x63, 0x77, 0x7F, 0x7F, 0x6B, 0x63, 0x63, 0x00], // 'M'
+    [0x63, 0x67, 0x6F, 0x7B, 0x73, 0x63, 0x63, 0x00], // 'N'
+    [0x1C, 0x36, 0x63, 0x63, 0x63, 0x36, 0x1C, 0x00], // 'O'
+    [0x3F, 0x66, 0x66, 0x3E, 0x06, 0x06, 0x0F, 0x00], // 'P'
+    [0x1E, 0x33, 0x33, 0x33, 0x3B, 0x1E, 0x38, 0x00], // 'Q'
+    [0x3F, 0x66, 0x66, 0x3E, 0x36, 0x66, 0x67, 0x00], // 'R'
+    [0x1E, 0x33, 0x07, 0x0E, 0x38, 0x33, 0x1E, 0x00], // 'S'
+    [0x3F, 0x2D, 0x0C, 0x0C, 0x0C, 0x0C, 0x1E, 0x00], // 'T'
+    [0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x3F, 0x00], // 'U'
+    [0x33, 0x33, 0x33, 0x33, 0x33, 0x1E, 0x0C, 0x00], // 'V'
+    [0x63, 0x63, 0x63, 0x6B, 0x7F, 0x77, 0x63, 0x00], // 'W'
+    [0x63, 0x63, 0x36, 0x1C, 0x1C, 0x36, 0x63, 0x00], // 'X'
+    [0x33, 0x33, 0x33, 0x1E, 0x0C, 0x0C, 0x1E, 0x00], // 'Y'
+    [0x7F, 0x63, 0x31, 0x18, 0x4C, 0x66, 0x7F, 0x00], // 'Z'
+    [0x1E, 0x06, 0x06, 0x06, 0x06, 0x06, 0x1E, 0x00], // '['
+    [0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x40, 0x00], // '\'
+    [0x1E, 0x18, 0x18, 0x18, 0x18, 0x18, 0x1E, 0x00], // ']'
+    [0x08, 0x1C, 0x36, 0x63, 0x00, 0x00, 0x00, 0x00], // '^'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF], // '_'
+    [0x0C, 0x0C, 0x18, 0x00, 0x00, 0x00, 0x00, 0x00], // '`'
+    [0x00, 0x00, 0x1E, 0x30, 0x3E, 0x33, 0x6E, 0x00], // 'a'
+    [0x07, 0x06, 0x06, 0x3E, 0x66, 0x66, 0x3B, 0x00], // 'b'
+    [0x00, 0x00, 0x1E, 0x33, 0x03, 0x33, 0x1E, 0x00], // 'c'
+    [0x38, 0x30, 0x30, 0x3E, 0x33, 0x33, 0x6E, 0x00], // 'd'
+    [0x00, 0x00, 0x1E, 0x33, 0x3F, 0x03, 0x1E, 0x00], // 'e'
+    [0x1C, 0x36, 0x06, 0x0F, 0x06, 0x06, 0x0F, 0x00], // 'f'
+    [0x00, 0x00, 0x6E, 0x33, 0x33, 0x3E, 0x30, 0x1F], // 'g'
+    [0x07, 0x06, 0x36, 0x6E, 0x66, 0x66, 0x67, 0x00], // 'h'
+    [0x0C, 0x00, 0x0E, 0x0C, 0x0C, 0x0C, 0x1E, 0x00], // 'i'
+    [0x30, 0x00, 0x30, 0x30, 0x30, 0x33, 0x33, 0x1E], // 'j'
+    [0x07, 0x06, 0x66, 0x36, 0x1E, 0x36, 0x67, 0x00], // 'k'
+    [0x0E, 0x0C, 0x0C, 0x0C, 0x0C, 0x0C, 0x1E, 0x00], // 'l'
+    [0x00, 0x00, 0x33, 0x7F, 0x7F, 0x6B, 0x63, 0x00], // 'm'
+    [0x00, 0x00, 0x1F, 0x33, 0x33, 0x33, 0x33, 0x00], // 'n'
+    [0x00, 0x00, 0x1E, 0x33, 0x33, 0x33, 0x1E, 0x00], // 'o'
+    [0x00, 0x00, 0x3B, 0x66, 0x66, 0x3E, 0x06, 0x0F], // 'p'
+    [0x00, 0x00, 0x6E, 0x33, 0x33, 0x3E, 0x30, 0x78], // 'q'
+    [0x00, 0x00, 0x3B, 0x6E, 0x66, 0x06, 0x0F, 0x00], // 'r'
+    [0x00, 0x00, 0x3E, 0x03, 0x1E, 0x30, 0x1F, 0x00], // 's'
+    [0x08, 0x0C, 0x3E, 0x0C, 0x0C, 0x2C, 0x18, 0x00], // 't'
+    [0x00, 0x00, 0x33, 0x33, 0x33, 0x33, 0x6E, 0x00], // 'u'
+    [0x00, 0x00, 0x33, 0x33, 0x33, 0x1E, 0x0C, 0x00], // 'v'
+    [0x00, 0x00, 0x63, 0x6B, 0x7F, 0x7F, 0x36, 0x00], // 'w'
+    [0x00, 0x00, 0x63, 0x36, 0x1C, 0x36, 0x63, 0x00], // 'x'
+    [0x00, 0x00, 0x33, 0x33, 0x33, 0x3E, 0x30, 0x1F], // 'y'
+    [0x00, 0x00, 0x3F, 0x19, 0x0C, 0x26, 0x3F, 0x00], // 'z'
+    [0x38, 0x0C, 0x0C, 0x07, 0x0C, 0x0C, 0x38, 0x00], // '{'
+    [0x18, 0x18, 0x18, 0x00, 0x18, 0x18, 0x18, 0x00], // '|'
+    [0x07, 0x0C, 0x0C, 0x38, 0x0C, 0x0C, 0x07, 0x00], // '}'
+    [0x6E, 0x3B, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '~'
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(glyph: &Glyph) -> Vec<String> {
+        (0..glyph.height())
+            .map(|y| {
+                (0..glyph.width())
+                    .map(|x| if glyph.get(x, y) { '#' } else { '.' })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn builtin_box_drawing() {
+        let font = BitmapFont::builtin();
+        let corner = rows(font.glyph(Char::new_must('┌')).unwrap());
+        assert_eq!(corner[7], "...#####");
+        assert_eq!(corner[15], "...#....");
+        assert_eq!(corner[0], "........");
+        let half = rows(font.glyph(Char::new_must('▄')).unwrap());
+        assert_eq!(half[7], "........");
+        assert_eq!(half[8], "########");
+        assert!(font.glyph(Char::new_must('A')).is_some());
+        assert!(font.glyph(Char::new_must('Ж')).is_none());
+    }
+
+    #[test]
+    fn psf2_with_unicode_table() {
+        let mut data = PSF2_MAGIC.to_vec();
+        for field in &[0u32, 32, 1, 2, 2, 2, 4] {
+            data.extend_from_slice(&field.to_le_bytes());
+        }
+        data.extend_from_slice(&[0x80, 0x40, 0xF0, 0xF0]);
+        data.extend_from_slice(b"x\xFEab\xFF");
+        data.extend_from_slice("yЖ".as_bytes());
+        data.push(0xFF);
+        let font = BitmapFont::from_psf(&data).unwrap();
+        assert_eq!((font.width(), font.height()), (4, 2));
+        assert_eq!(
+            rows(font.glyph(Char::new_must('x')).unwrap()),
+            vec!["#...", ".#.."]
+        );
+        assert_eq!(
+            rows(font.glyph(Char::new_must('Ж')).unwrap()),
+            vec!["####", "####"]
+        );
+        assert!(font.glyph(Char::new_must('a')).is_none());
+        assert!(font.glyph(Char::new_must('─')).is_some());
+    }
+
+    #[test]
+    fn psf1_without_unicode_table() {
+        let mut data = PSF1_MAGIC.to_vec();
+        data.extend_from_slice(&[0, 1]);
+        data.extend((0..=255).map(|i: u32| if i == 'A' as u32 { 0x81 } else { 0 }));
+        let font = BitmapFont::from_psf(&data).unwrap();
+        assert_eq!(
+            rows(font.glyph(Char::new_must('A')).unwrap()),
+            vec!["#......#"]
+        );
+        assert!(BitmapFont::from_psf(&data[..100]).is_err());
+    }
+
+    #[test]
+    fn bdf_glyph_placement() {
+        let bdf = "STARTFONT 2.1\nFONTBOUNDINGBOX 4 4 0 -1\nSTARTPROPERTIES 2\n\
+            FONT_ASCENT 3\nFONT_DESCENT 1\nENDPROPERTIES\nCHARS 1\nSTARTCHAR dot\n\
+            ENCODING 46\nBBX 2 2 1 0\nBITMAP\nC0\n40\nENDCHAR\nENDFONT\n";
+        let font = BitmapFont::from_bdf(bdf).unwrap();
+        assert_eq!((font.width(), font.height()), (4, 4));
+        assert_eq!(
+            rows(font.glyph(Char::new_must('.')).unwrap()),
+            vec!["....", ".##.", "..#.", "...."]
+        );
+    }
+
+    #[test]
+    fn bdf_malformed() {
+        let bdf = "STARTFONT 2.1\nFONTBOUNDINGBOX 4 4 0 -1\nFONT_ASCENT\nENDFONT\n";
+        assert!(BitmapFont::from_bdf(bdf).is_err());
+        let bdf = "STARTFONT 2.1\nFONTBOUNDINGBOX 4 4 0 -1\nSTARTCHAR wide\n\
+            ENCODING 46\nBBX 80 1 0 0\nBITMAP\nFF\nENDCHAR\nENDFONT\n";
+        assert!(BitmapFont::from_bdf(bdf).is_err());
+        let bdf = "STARTFONT 2.1\nFONTBOUNDINGBOX 4 4 -9223372036854775808 -1\n\
+            STARTCHAR dot\nENCODING 46\nBBX 2 2 1 0\nBITMAP\nC0\n40\nENDCHAR\nENDFONT\n";
+        assert!(BitmapFont::from_bdf(bdf).is_err());
+        let bdf = "STARTFONT 2.1\nFONTBOUNDINGBOX 3000 3000 0 0\n\
+            STARTCHAR dot\nENCODING 46\nBBX 2 2 1 0\nBITMAP\nC0\n40\nENDCHAR\nENDFONT\n";
+        assert!(BitmapFont::from_bdf(bdf).is_err());
+
+        let mut psf = PSF2_MAGIC.to_vec();
+        for field in &[0u32, 32, 0, 0, 3000 * 375, 3000, 3000] {
+            psf.extend_from_slice(&field.to_le_bytes());
+        }
+        assert!(BitmapFont::from_psf(&psf).is_err());
+    }
+}
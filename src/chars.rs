@@ -10,6 +10,11 @@ pub const UNDERSCORE: Char = Char { char: '_' };
 /// A validated character for use in 3a art.
 /// Only allowed characters (printable, non‑control, etc.) can be contained.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "String", into = "String")
+)]
 pub struct Char {
     pub(crate) char: char,
 }
@@ -68,6 +68,19 @@ impl Color {
         }
     }
 
+    /// Converts color to a legacy 3a color digit, if it is one of the 16
+    /// basic colors. No color maps to the usual terminal default:
+    /// white for foreground and black for background.
+    pub fn to_legacy(self, foreground: bool) -> Option<char> {
+        let index = match self {
+            Self::None => return Some(if foreground { '7' } else { '0' }),
+            Self::Color4(c, bright) => c as u32 + if bright { 8 } else { 0 },
+            Self::Color256(c) if c < 16 => c as u32,
+            _ => return None,
+        };
+        std::char::from_digit(index, 16).map(trans_color)
+    }
+
     /// Converts color to durdraw 256 color code.
     /// Solves some obscure color remappings in durdraw.
     pub fn to_durdraw_color(&self) -> usize {
@@ -166,6 +179,27 @@ impl FromStr for Color {
     }
 }
 
+/// Serializes no color as none and other colors as in palette entries.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Color {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Color::None => serializer.serialize_none(),
+            color => serializer.serialize_some(&color.to_string()),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Color {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match Option::<String>::deserialize(deserializer)? {
+            Some(color) => color.parse().map_err(serde::de::Error::custom),
+            None => Ok(Color::None),
+        }
+    }
+}
+
 /// Formats the color as a string (color name, index, or hex RGB).
 impl fmt::Display for Color {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -249,6 +283,7 @@ impl Color {
 
 /// A pair of foreground and background colors.
 #[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ColorPair {
     pub fg: Color,
     pub bg: Color,
@@ -335,6 +370,8 @@ impl FromStr for ColorPair {
 
 /// A mapping from character codes to color pairs, with optional comments per entry.
 #[derive(Default, Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct Palette {
     pub palette: OrderMap<Char, (ColorPair, Comments)>,
 }
@@ -368,6 +405,21 @@ impl Palette {
         }
         None
     }
+    /// Returns the name of a color pair, adding it to the palette under
+    /// an unused name if neither an entry nor a built-in name matches.
+    pub(crate) fn name_color(&mut self, col: ColorPair) -> Result<Char, Error> {
+        if let Some(name) = self.search_color(col) {
+            return Ok(name);
+        }
+        let name = ('g'..='z')
+            .chain('A'..='Z')
+            .chain('\u{c0}'..='\u{24f}')
+            .filter_map(|c| Char::new(c).ok())
+            .find(|name| !self.palette.contains_key(name))
+            .ok_or_else(|| Error::ColorName(col.to_string()))?;
+        self.palette.insert(name, (col, Vec::new()));
+        Ok(name)
+    }
     /// Checks if a character code is defined in the palette.
     pub fn contains_color(&self, name: Char) -> bool {
         self.palette.contains_key(&name)
@@ -494,6 +546,13 @@ impl CSSColorMap {
             }
         }
     }
+    /// Returns the RGB components for a color and foreground flag.
+    /// Mapped values that are not `#rgb` or `#rrggbb` hex colors fall back to built-in defaults.
+    pub fn map_rgb(&self, color: Color, foreground: bool) -> (u8, u8, u8) {
+        parse_hex_color(&self.map(color, foreground))
+            .or_else(|| parse_hex_color(&CSSColorMap::default().map(color, foreground)))
+            .unwrap_or((0, 0, 0))
+    }
 }
 
 #[cfg(test)]
@@ -564,6 +623,17 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_css_map_rgb() {
+        let mut map = CSSColorMap::default();
+        assert_eq!(map.map_rgb(Color::None, true), (255, 255, 255));
+        assert_eq!(map.map_rgb(Color::Color256(196), true), (255, 0, 0));
+        map.map.insert((Color::None, false), "#123".into());
+        map.map.insert((Color::None, true), "white".into());
+        assert_eq!(map.map_rgb(Color::None, false), (0x11, 0x22, 0x33));
+        assert_eq!(map.map_rgb(Color::None, true), (255, 255, 255));
+    }
+
     #[test]
     fn test_none_color_resets() {
         assert_eq!(Color::None.to_ansi(true), "\x1b[39m");
@@ -571,6 +641,23 @@ mod tests {
     }
 }
 
+/// Parses `#rgb` and `#rrggbb` hex colors.
+fn parse_hex_color(s: &str) -> Option<(u8, u8, u8)> {
+    let hex = s.strip_prefix('#')?;
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let channel = |i: usize, len: usize| {
+        let v = u8::from_str_radix(&hex[i * len..(i + 1) * len], 16).ok()?;
+        Some(if len == 1 { v * 17 } else { v })
+    };
+    match hex.len() {
+        3 => Some((channel(0, 1)?, channel(1, 1)?, channel(2, 1)?)),
+        6 => Some((channel(0, 2)?, channel(1, 2)?, channel(2, 2)?)),
+        _ => None,
+    }
+}
+
 pub(crate) fn trans_color(leacy: char) -> char {
     match leacy {
         '0' => '0',
@@ -5,19 +5,29 @@ use std::{
 };
 
 use crate::{
+    bitmap::BitmapFont,
     chars::{normalize_text, Char, SPACE, UNDERSCORE},
     colors::{trans_color, CSSColorMap, ColorPair, Palette},
     delay::Delay,
-    error::{Error, Result},
+    error::{Error, Result, Span},
     font::Font,
     header::{Header, LegacyColorMode, LegacyHeaderInfo},
-    helpers::{escape_html, in_range, timing_for_svg},
+    helpers::{escape_html, in_range},
+    lines::Lines,
+    png,
+    raster::render_frame,
+    svg::{
+        class_name, color_style, css_keyframes, smil_key_times, smil_values, unchanged_runs,
+        SvgAnimation, SvgOptions,
+    },
+    timeline::Timeline,
     Color,
 };
 
 /// A single cell in a frame, containing a text character
 /// and an optional color character.
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Cell {
     /// The text character displayed in this cell.
     pub text: Char,
@@ -66,6 +76,11 @@ impl Cell {
 
 /// A single frame of 3a art, consisting of a grid of cells.
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "FrameRows", into = "FrameRows")
+)]
 pub struct Frame {
     pub(crate) color: usize,
     pub(crate) width: usize,
@@ -97,52 +112,116 @@ pub fn merge_frames(text: &Frame, color: &Frame) -> Result<Frame> {
 // SVG
 impl Frame {
     /// Generates SVG background rectangles for colored cells.
+    /// Horizontal runs of one background color are merged into a single rectangle.
     pub fn to_svg_frame_bg(&self, palette: &Palette, map: &CSSColorMap, font: &Font) -> String {
+        self.svg_bg(palette, font, |_, bg| {
+            format!(" fill=\"{}\"", map.map(bg, false))
+        })
+    }
+
+    /// Like [`Frame::to_svg_frame_bg`], filling rectangles through the
+    /// palette entry classes of [`color_style`].
+    fn svg_bg_classes(&self, palette: &Palette, font: &Font) -> String {
+        self.svg_bg(palette, font, |name, _| {
+            format!(" class=\"{}\"", class_name(name, false))
+        })
+    }
+
+    /// Generates background rectangles, `fill` gives the attributes coloring
+    /// a run of one palette entry and color.
+    fn svg_bg<F: Fn(Char, Color) -> String>(
+        &self,
+        palette: &Palette,
+        font: &Font,
+        fill: F,
+    ) -> String {
         let mut txt = String::new();
         for r in 0..self.height() {
-            for c in 0..self.width() {
-                if let Some(name) = self.rows[r][c].color {
-                    let bg = palette.get_color(name).bg;
-                    if bg == Color::None {
+            let mut run: Option<(usize, Char, Color)> = None;
+            for c in 0..=self.width() {
+                let cell = self.rows[r].get(c).and_then(|cell| cell.color);
+                let bg = cell.map(|name| (name, palette.get_color(name).bg));
+                let bg = bg.filter(|(_, bg)| *bg != Color::None);
+                if let Some((start, name, color)) = run {
+                    if bg.map(|(_, bg)| bg) == Some(color) {
                         continue;
                     }
-                    let fill = map.map(bg, false);
-                    let x = font.width * c;
-                    let y = font.height * r;
-                    // TODO: Optimise sequences
                     txt += &format!(
-                        "<rect x=\"{}\"  y=\"{}\"  width=\"{}\" height=\"{}\" fill=\"{}\"/>\n",
-                        x, y, font.width, font.height, fill
+                        "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"{}/>\n",
+                        font.width * start,
+                        font.height * r,
+                        font.width * (c - start),
+                        font.height,
+                        fill(name, color)
                     );
-                };
+                }
+                run = bg.map(|(name, bg)| (c, name, bg));
             }
         }
         txt
     }
 
     /// Generates SVG text with foreground colors.
+    /// Horizontal runs of one foreground color are merged into a single span;
+    /// spaces join any run.
     pub fn to_svg_frame_txt_fg(&self, palette: &Palette, map: &CSSColorMap, font: &Font) -> String {
-        let mut txt =
+        self.svg_txt_fg(palette, font, |_, fg| {
+            format!(" fill=\"{}\"", map.map(fg, true))
+        })
+    }
+
+    /// Like [`Frame::to_svg_frame_txt_fg`], coloring spans through the
+    /// palette entry classes of [`color_style`].
+    fn svg_txt_fg_classes(&self, palette: &Palette, font: &Font) -> String {
+        self.svg_txt_fg(palette, font, |name, _| match name {
+            Some(name) => format!(" class=\"{}\"", class_name(name, true)),
+            None => String::new(),
+        })
+    }
+
+    /// Generates text spans, `fill` gives the attributes coloring a run of
+    /// one palette entry (`None` for the default color) and color.
+    fn svg_txt_fg<F: Fn(Option<Char>, Color) -> String>(
+        &self,
+        palette: &Palette,
+        font: &Font,
+        fill: F,
+    ) -> String {
+        let mut txt: String =
             "<text x=\"0\" y=\"0\" xml:space=\"preserve\" dominant-baseline=\"hanging\">\n".into();
         for r in 0..self.height() {
-            for c in 0..self.width() {
-                let fg = if let Some(name) = self.rows[r][c].color {
-                    Some(palette.get_color(name).fg)
-                } else {
-                    None
-                };
-                let fill = map.map_opt(fg, true);
-                let x = font.width * c + font.fg_offset_x;
-                let y = font.height * r + font.fg_offset_y;
-                // TODO: Optimise sequences
-                let span = format!(
-                    "<tspan x=\"{}\" y=\"{}\" fill=\"{}\">{}</tspan>\n",
-                    x,
-                    y,
-                    fill,
-                    escape_html(&self.rows[r][c].text.to_string()),
-                );
-                txt += span.as_str();
+            // Start column, end column past the last non-space, class and color.
+            let mut run: Option<(usize, usize, Option<Char>, Color)> = None;
+            for c in 0..=self.width() {
+                let cell = self.rows[r].get(c);
+                if let Some(cell) = cell {
+                    if cell.text == SPACE {
+                        continue;
+                    }
+                }
+                let fg = cell.map(|cell| {
+                    let fg = cell.color.map(|name| palette.get_color(name).fg);
+                    let fg = fg.unwrap_or(Color::None);
+                    (cell.color.filter(|_| fg != Color::None), fg)
+                });
+                if let Some((start, end, name, color)) = run {
+                    if fg.map(|(_, fg)| fg) == Some(color) {
+                        run = Some((start, c + 1, name, color));
+                        continue;
+                    }
+                    let text: String = self.rows[r][start..end]
+                        .iter()
+                        .map(|cell| cell.text.char)
+                        .collect();
+                    txt += &format!(
+                        "<tspan x=\"{}\" y=\"{}\"{}>{}</tspan>\n",
+                        font.width * start + font.fg_offset_x,
+                        font.height * r + font.fg_offset_y,
+                        fill(name, color),
+                        escape_html(&text),
+                    );
+                }
+                run = fg.map(|(name, fg)| (c, c + 1, name, fg));
             }
         }
         txt += "</text>\n";
@@ -187,6 +266,29 @@ impl Frame {
         }
     }
 
+    /// Like [`Frame::to_svg_frame`], coloring through the palette entry
+    /// classes of [`color_style`].
+    fn svg_frame_classes(&self, colors: bool, palette: &Palette, font: &Font) -> String {
+        if colors {
+            self.svg_bg_classes(palette, font) + &self.svg_txt_fg_classes(palette, font)
+        } else {
+            self.to_svg_frame_txt(font)
+        }
+    }
+
+    /// Renders this frame to a PNG image, optionally with colors.
+    /// Cells take the geometry of `font` and glyphs are drawn from `glyphs`.
+    pub fn to_png(
+        &self,
+        colors: bool,
+        palette: &Palette,
+        map: &CSSColorMap,
+        font: &Font,
+        glyphs: &BitmapFont,
+    ) -> Vec<u8> {
+        png::encode(&render_frame(self, colors, palette, map, font, glyphs))
+    }
+
     /// Generates a complete SVG document for this frame.
     pub fn to_svg(
         &self,
@@ -205,6 +307,7 @@ impl Frame {
         .as_str();
         svg += font.to_style().as_str();
         if colors {
+            svg += color_style(slice::from_ref(self), palette, map).as_str();
             svg += format!(
                 "<rect x=\"0\"  y=\"0\"  width=\"{}\" height=\"{}\" fill=\"{}\"/>\n",
                 width,
@@ -213,7 +316,7 @@ impl Frame {
             )
             .as_str();
         }
-        svg += self.to_svg_frame(colors, palette, map, font).as_str();
+        svg += self.svg_frame_classes(colors, palette, font).as_str();
         svg += "</svg>\n";
         svg
     }
@@ -222,53 +325,83 @@ impl Frame {
 impl Frame {
     /// Reads a color-only frame from input lines.
     pub fn read_color<R: Read>(lines: &mut io::Lines<BufReader<R>>) -> Result<Self> {
+        Self::read_color_lines(&mut Lines::new(lines))
+    }
+
+    /// Reads a text-only frame from input lines.
+    pub fn read_text<R: Read>(lines: &mut io::Lines<BufReader<R>>) -> Result<Self> {
+        Self::read_text_lines(&mut Lines::new(lines))
+    }
+
+    /// Reads a combined (text+color) frame from input lines.
+    pub fn read_both<R: Read>(lines: &mut io::Lines<BufReader<R>>) -> Result<Self> {
+        Self::read_both_lines(&mut Lines::new(lines))
+    }
+
+    /// Reads a color-only frame like [`Frame::read_color`] from [`Lines`],
+    /// which counts lines for error spans and carries the parse options.
+    pub fn read_color_lines<I: Iterator<Item = io::Result<String>>>(
+        lines: &mut Lines<I>,
+    ) -> Result<Self> {
+        let fill = Cell {
+            text: SPACE,
+            ..lines.options().pad_with
+        };
         let mut width: usize = 0;
         let mut rows: Vec<Vec<Cell>> = Vec::new();
-        let mut color: usize = 0;
-        for line in lines {
+        while let Some(line) = lines.next() {
             let line = normalize_text(line?.as_str());
             if line.is_empty() {
                 break;
             }
-            let full_line: Vec<char> = line.chars().collect();
-            if width != 0 && full_line.len() != width {
-                return Err(Error::WidthMismatch);
-            }
-            width = full_line.len();
             let mut row: Vec<Cell> = Vec::new();
-            for c in full_line {
+            for c in line.chars() {
                 row.push(Cell {
                     text: SPACE,
                     color: Some(Char::new_must(c)),
                 });
-                color += 1;
             }
+            if rows.is_empty() {
+                width = row.len();
+            }
+            fit_row(lines, &mut row, width, fill)?;
             rows.push(row);
         }
-        Ok(Self { width, color, rows })
+        let mut frame = Self {
+            width,
+            color: 0,
+            rows,
+        };
+        frame.recalc_colors();
+        Ok(frame)
     }
 
-    /// Reads a text-only frame from input lines.
-    pub fn read_text<R: Read>(lines: &mut io::Lines<BufReader<R>>) -> Result<Self> {
+    /// Reads a text-only frame like [`Frame::read_text`] from [`Lines`].
+    pub fn read_text_lines<I: Iterator<Item = io::Result<String>>>(
+        lines: &mut Lines<I>,
+    ) -> Result<Self> {
+        let fill = Cell {
+            color: None,
+            ..lines.options().pad_with
+        };
         let mut width: usize = 0;
         let mut rows: Vec<Vec<Cell>> = Vec::new();
-        for line in lines {
+        while let Some(line) = lines.next() {
             let line = normalize_text(line?.as_str());
             if line.is_empty() {
                 break;
             }
-            let full_line: Vec<char> = line.chars().collect();
-            if width != 0 && full_line.len() != width {
-                return Err(Error::WidthMismatch);
-            }
-            width = full_line.len();
             let mut row: Vec<Cell> = Vec::new();
-            for c in full_line {
+            for c in line.chars() {
                 row.push(Cell {
                     text: Char::new_must(c),
                     color: None,
                 });
             }
+            if rows.is_empty() {
+                width = row.len();
+            }
+            fit_row(lines, &mut row, width, fill)?;
             rows.push(row);
         }
         Ok(Self {
@@ -278,40 +411,91 @@ impl Frame {
         })
     }
 
-    /// Reads a combined (text+color) frame from input lines.
-    pub fn read_both<R: Read>(lines: &mut io::Lines<BufReader<R>>) -> Result<Self> {
+    /// Reads a combined (text+color) frame like [`Frame::read_both`] from [`Lines`].
+    /// In lenient mode the text half of a ragged row takes the frame width
+    /// and the color half is padded or truncated to match it.
+    pub fn read_both_lines<I: Iterator<Item = io::Result<String>>>(
+        lines: &mut Lines<I>,
+    ) -> Result<Self> {
+        let lenient = lines.options().lenient;
+        let pad = lines.options().pad_with;
         let mut width: usize = 0;
         let mut rows: Vec<Vec<Cell>> = Vec::new();
-        let mut color: usize = 0;
-        for line in lines {
+        while let Some(line) = lines.next() {
             let line = normalize_text(line?.as_str());
             if line.is_empty() {
                 break;
             }
             let full_line: Vec<char> = line.chars().collect();
-            if width != 0 && full_line.len() / 2 != width {
-                return Err(Error::WidthMismatch);
+            if rows.is_empty() {
+                width = (full_line.len() + 1) / 2;
             }
-            width = full_line.len() / 2;
-            let mut row: Vec<Cell> = Vec::new();
-            let text = &full_line[..full_line.len() / 2];
-            let colors = &full_line[full_line.len() / 2..];
-            if text.len() != colors.len() {
-                return Err(Error::WidthMismatch);
+            if full_line.len() != width * 2 {
+                if !lenient {
+                    let column = width.min(full_line.len() / 2) + 1;
+                    return Err(lines.locate(Error::WidthMismatch, Some(column)));
+                }
+                let message = if full_line.len() > width * 2 {
+                    format!("truncated {} extra columns", full_line.len() - width * 2)
+                } else {
+                    format!(
+                        "filled {} missing color columns",
+                        width * 2 - full_line.len()
+                    )
+                };
+                lines.fix(
+                    Some(full_line.len().min(width * 2) + 1),
+                    Error::WidthMismatch,
+                    message,
+                );
             }
-            for i in 0..text.len() {
+            let split = width.min(full_line.len());
+            let text = &full_line[..split];
+            let colors = &full_line[split..full_line.len().min(width * 2)];
+            let mut row: Vec<Cell> = Vec::new();
+            for i in 0..width {
                 row.push(Cell {
-                    text: Char::new_must(text[i]),
-                    color: Some(Char::new_must(colors[i])),
+                    text: text.get(i).map(|c| Char::new_must(*c)).unwrap_or(pad.text),
+                    color: colors.get(i).map(|c| Char::new_must(*c)).or(pad.color),
                 });
-                color += 1;
             }
             rows.push(row);
         }
-        Ok(Self { width, color, rows })
+        let mut frame = Self {
+            width,
+            color: 0,
+            rows,
+        };
+        frame.recalc_colors();
+        Ok(frame)
     }
 }
 
+/// Checks that a row is `width` cells wide.
+/// In lenient mode the row is padded with `fill` or truncated instead.
+fn fit_row<I: Iterator<Item = io::Result<String>>>(
+    lines: &mut Lines<I>,
+    row: &mut Vec<Cell>,
+    width: usize,
+    fill: Cell,
+) -> Result<()> {
+    if row.len() == width {
+        return Ok(());
+    }
+    let column = width.min(row.len()) + 1;
+    if !lines.options().lenient {
+        return Err(lines.locate(Error::WidthMismatch, Some(column)));
+    }
+    let message = if row.len() > width {
+        format!("truncated row from {} to {} cells", row.len(), width)
+    } else {
+        format!("padded row from {} to {} cells", row.len(), width)
+    };
+    lines.fix(Some(column), Error::WidthMismatch, message);
+    row.resize(width, fill);
+    Ok(())
+}
+
 impl Frame {
     /// Returns true if the frame contains any color cells.
     pub fn color(&self) -> bool {
@@ -646,6 +830,33 @@ impl Frame {
         }
     }
 
+    /// Creates a frame from rows of the text and color channels,
+    /// with `_` for cells without color, as in a 3a body.
+    pub fn from_rows<S: AsRef<str>>(text: &[S], colors: &[S]) -> Result<Self> {
+        if colors.len() != text.len() {
+            return Err(Error::HeightMismatch);
+        }
+        let width = text.first().map_or(0, |row| row.as_ref().chars().count());
+        let mut frame = Frame::new(width, text.len(), Cell::default());
+        for (r, (text, colors)) in text.iter().zip(colors).enumerate() {
+            let (text, colors) = (text.as_ref(), colors.as_ref());
+            if text.chars().count() != width || colors.chars().count() != width {
+                return Err(Error::WidthMismatch);
+            }
+            for (c, (text, color)) in text.chars().zip(colors.chars()).enumerate() {
+                frame.rows[r][c] = Cell {
+                    text: Char::new(text)?,
+                    color: match color {
+                        '_' => None,
+                        color => Some(Char::new(color)?),
+                    },
+                };
+            }
+        }
+        frame.recalc_colors();
+        Ok(frame)
+    }
+
     /// Formats the frame's text channel.
     pub fn fmt_text(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for row in &self.rows {
@@ -685,6 +896,39 @@ impl Frame {
         Ok(())
     }
 
+    /// Writes the frame in legacy 3a format: each row is a line of text
+    /// followed by foreground and background color digits as `mode` requires.
+    pub(crate) fn write_legacy(
+        &self,
+        out: &mut String,
+        mode: LegacyColorMode,
+        palette: &Palette,
+    ) -> Result<()> {
+        let channels: &[bool] = match mode {
+            LegacyColorMode::None => &[],
+            LegacyColorMode::FgOnly => &[true],
+            LegacyColorMode::BgOnly => &[false],
+            LegacyColorMode::FgAndBg => &[true, false],
+        };
+        for row in &self.rows {
+            for cell in row {
+                out.push(cell.text.into());
+            }
+            for &foreground in channels {
+                for cell in row {
+                    let pair = cell.to_pair(palette);
+                    let color = if foreground { pair.fg } else { pair.bg };
+                    let digit = color.to_legacy(foreground).ok_or_else(|| {
+                        Error::LegacyColor(cell.color.unwrap_or(UNDERSCORE).into())
+                    })?;
+                    out.push(digit);
+                }
+            }
+            out.push('\n');
+        }
+        Ok(())
+    }
+
     /// Formats the frame with optional color inclusion.
     pub fn fmt_with_colors(
         &self,
@@ -709,6 +953,40 @@ impl Frame {
     }
 }
 
+/// Serialized form of a frame: rows of the text and color channels,
+/// with `_` for cells without color, as in a 3a body.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct FrameRows {
+    text: Vec<String>,
+    colors: Vec<String>,
+}
+
+#[cfg(feature = "serde")]
+impl From<Frame> for FrameRows {
+    fn from(frame: Frame) -> Self {
+        let rows = |channel: fn(&Cell) -> char| -> Vec<String> {
+            frame
+                .rows
+                .iter()
+                .map(|row| row.iter().map(channel).collect())
+                .collect()
+        };
+        Self {
+            text: rows(|cell| cell.text.char),
+            colors: rows(|cell| cell.color.unwrap_or(UNDERSCORE).char),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl std::convert::TryFrom<FrameRows> for Frame {
+    type Error = Error;
+    fn try_from(data: FrameRows) -> Result<Self> {
+        Frame::from_rows(&data.text, &data.colors)
+    }
+}
+
 /// Displays the frame in combined text+color format.
 impl fmt::Display for Frame {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -718,6 +996,11 @@ impl fmt::Display for Frame {
 
 /// A collection of frames forming an animation, with optional pinned channels.
 #[derive(Default, Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "FramesData", into = "FramesData")
+)]
 pub struct Frames {
     pub(crate) text_pin: Option<Frame>,
     pub(crate) color_pin: Option<Frame>,
@@ -1180,9 +1463,40 @@ impl Frames {
         map: &CSSColorMap,
         font: &Font,
         delays: &Delay,
+    ) -> String {
+        self.to_svg_frames_with(colors, palette, map, font, delays, &SvgOptions::default())
+    }
+
+    /// Generates an animated SVG from all frames with the given options.
+    pub fn to_svg_frames_with(
+        &self,
+        colors: bool,
+        palette: &Palette,
+        map: &CSSColorMap,
+        font: &Font,
+        delays: &Delay,
+        options: &SvgOptions,
     ) -> String {
         let delays = delays.to_vec_delays(self.frames());
-        let (total_s, key_times, delays) = timing_for_svg(&delays);
+        let runs = if options.skip_unchanged {
+            unchanged_runs(&self.frames, &delays)
+        } else {
+            delays.into_iter().enumerate().collect()
+        };
+        let delays: Vec<usize> = runs.iter().map(|(_, delay)| *delay).collect();
+        let timeline = Timeline::new(&delays);
+        let css = options.animation == SvgAnimation::Css;
+        let keyframes = if css {
+            css_keyframes(&timeline, options.plays)
+        } else {
+            String::new()
+        };
+        let total_s = timeline.duration_ms() as f64 / 1000.0;
+        let key_times = smil_key_times(&timeline);
+        let repeat_count = match options.plays {
+            Some(plays) => plays.to_string(),
+            None => "indefinite".to_string(),
+        };
         let mut svg = "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n".into();
         let width = self.width() * font.width;
         let height = self.height() * font.height;
@@ -1193,6 +1507,7 @@ impl Frames {
         .as_str();
         svg += font.to_style().as_str();
         if colors {
+            svg += color_style(&self.frames, palette, map).as_str();
             svg += format!(
                 "<rect x=\"0\"  y=\"0\"  width=\"{}\" height=\"{}\" fill=\"{}\"/>\n",
                 width,
@@ -1201,35 +1516,38 @@ impl Frames {
             )
             .as_str();
         }
+        svg += keyframes.as_str();
         svg += "\n";
         let (_, color_pinned) = self.pinned();
-        if colors && self.color() && color_pinned {
-            svg += self.frames[0].to_svg_frame_bg(palette, map, font).as_str();
-            for f in 0..self.frames() {
+        let pinned_bg = colors && self.color() && color_pinned;
+        if pinned_bg {
+            svg += self.frames[0].svg_bg_classes(palette, font).as_str();
+        }
+        for (i, (f, _)) in runs.iter().enumerate() {
+            if css {
+                svg += format!("<g opacity=\"0\" class=\"a3-f{}\">\n", i).as_str();
+            } else {
                 svg += "<g opacity=\"0\">\n";
-                svg += self.frames[f]
-                    .to_svg_frame_txt_fg(palette, map, font)
-                    .as_str();
-                svg += format!(
-                "<animate attributeName=\"opacity\" begin=\"0s\" dur=\"{}s\" repeatCount=\"indefinite\" calcMode=\"discrete\" values=\"{}\" keyTimes=\"{}\" />\n",
-                total_s, delays[f], key_times
-            )
-            .as_str();
-                svg += "</g>\n\n";
             }
-        } else {
-            for f in 0..self.frames() {
-                svg += "<g opacity=\"0\">\n";
-                svg += self.frames[f]
-                    .to_svg_frame(colors, palette, map, font)
+            if pinned_bg {
+                svg += self.frames[*f].svg_txt_fg_classes(palette, font).as_str();
+            } else {
+                svg += self.frames[*f]
+                    .svg_frame_classes(colors, palette, font)
                     .as_str();
+            }
+            if !css {
                 svg += format!(
-                "<animate attributeName=\"opacity\" begin=\"0s\" dur=\"{}s\" repeatCount=\"indefinite\" calcMode=\"discrete\" values=\"{}\" keyTimes=\"{}\" />\n",
-                total_s, delays[f], key_times
-            )
-            .as_str();
-                svg += "</g>\n\n";
+                    "<animate attributeName=\"opacity\" begin=\"0s\" dur=\"{}s\" repeatCount=\"{}\"{} calcMode=\"discrete\" values=\"{}\" keyTimes=\"{}\" />\n",
+                    total_s,
+                    repeat_count,
+                    if options.plays.is_some() { " fill=\"freeze\"" } else { "" },
+                    smil_values(i, runs.len(), options.plays.is_some()),
+                    key_times
+                )
+                .as_str();
             }
+            svg += "</g>\n\n";
         }
         svg += "</svg>\n";
         svg
@@ -1237,49 +1555,78 @@ impl Frames {
 }
 
 impl Frames {
-    pub(crate) fn read_text_pin<R: Read>(
+    pub(crate) fn read_text_pin<I: Iterator<Item = io::Result<String>>>(
         &mut self,
-        lines: &mut io::Lines<BufReader<R>>,
+        lines: &mut Lines<I>,
     ) -> Result<()> {
         if self.text_pin != None {
-            return Err(Error::BlockDup("text-pin".into()));
+            return Err(lines.locate_block(Error::BlockDup("text-pin".into()), "text-pin", None));
         }
-        let frame = Frame::read_text(lines)?;
+        let first = lines.line() + 1;
+        let fixes = lines.fixes().len();
+        let mut frame = Frame::read_text_lines(lines)
+            .map_err(|err| lines.locate_block(err, "text-pin", None))?;
         if frame.width() != 0 && frame.height() != 0 {
+            let span = Span {
+                line: first,
+                block: Some("text-pin".into()),
+                ..Span::default()
+            };
+            if lines.options().lenient {
+                self.fit_frame(&mut frame, lines, span.clone())?;
+            }
+            lines.locate_fixes(fixes, &span);
             self.text_pin = Some(frame)
         }
         Ok(())
     }
-    pub(crate) fn read_color_pin<R: Read>(
+    pub(crate) fn read_color_pin<I: Iterator<Item = io::Result<String>>>(
         &mut self,
-        lines: &mut io::Lines<BufReader<R>>,
+        lines: &mut Lines<I>,
     ) -> Result<()> {
         if self.color_pin != None {
-            return Err(Error::BlockDup("color-pin".into()));
+            return Err(lines.locate_block(Error::BlockDup("color-pin".into()), "color-pin", None));
         }
-        let frame = Frame::read_color(lines)?;
+        let first = lines.line() + 1;
+        let fixes = lines.fixes().len();
+        let mut frame = Frame::read_color_lines(lines)
+            .map_err(|err| lines.locate_block(err, "color-pin", None))?;
         if frame.width() != 0 && frame.height() != 0 {
+            let span = Span {
+                line: first,
+                block: Some("color-pin".into()),
+                ..Span::default()
+            };
+            if lines.options().lenient {
+                self.fit_frame(&mut frame, lines, span.clone())?;
+            }
+            lines.locate_fixes(fixes, &span);
             self.color_pin = Some(frame)
         }
         Ok(())
     }
 
     pub(crate) fn merge(&mut self) -> Result<()> {
-        if let Some(color_pin) = &self.color_pin {
-            for i in 0..self.frames.len() {
-                self.frames[i] = merge_frames(&self.frames[i], &color_pin)?;
-            }
-        }
-        if let Some(text_pin) = &self.text_pin {
-            for i in 0..self.frames.len() {
-                self.frames[i] = merge_frames(&text_pin, &self.frames[i])?;
-            }
+        for i in 0..self.frames.len() {
+            self.frames[i] = self.pin_frame(&self.frames[i])?;
         }
         self.color_pin = None;
         self.text_pin = None;
         Ok(())
     }
 
+    /// Applies the pins to a single frame.
+    pub(crate) fn pin_frame(&self, frame: &Frame) -> Result<Frame> {
+        let mut frame = match &self.color_pin {
+            Some(color_pin) => merge_frames(frame, color_pin)?,
+            None => frame.clone(),
+        };
+        if let Some(text_pin) = &self.text_pin {
+            frame = merge_frames(text_pin, &frame)?;
+        }
+        Ok(frame)
+    }
+
     pub(crate) fn check_frame(&mut self, frame: &Frame) -> Result<()> {
         if self.width != 0 && self.width != frame.width() {
             return Err(Error::WidthMismatch);
@@ -1292,70 +1639,97 @@ impl Frames {
         Ok(())
     }
 
-    pub(crate) fn read_body<R: Read>(
+    pub(crate) fn read_body<I: Iterator<Item = io::Result<String>>>(
         &mut self,
-        lines: &mut io::Lines<BufReader<R>>,
+        lines: &mut Lines<I>,
         header: &Header,
     ) -> Result<()> {
-        if !header.get_colors() {
-            self.read_body_text(lines)
-        } else if self.color_pin != None {
-            self.read_body_text(lines)
-        } else if self.text_pin != None {
-            self.read_body_color(lines)
-        } else {
-            self.read_body_both(lines)
-        }
+        let fixes = lines.fixes().len();
+        let result = self.read_body_frames(lines, header);
+        let span = Span {
+            block: Some("body".into()),
+            ..Span::default()
+        };
+        lines.locate_fixes(fixes, &span);
+        result.map_err(|err| lines.locate_block(err, "body", None))
     }
 
-    pub(crate) fn read_body_both<R: Read>(
+    /// Checks the frame size against the frames read before.
+    /// In lenient mode the frame is resized to match instead.
+    fn fit_frame<I: Iterator<Item = io::Result<String>>>(
         &mut self,
-        lines: &mut io::Lines<BufReader<R>>,
+        frame: &mut Frame,
+        lines: &mut Lines<I>,
+        span: Span,
     ) -> Result<()> {
-        loop {
-            let frame = Frame::read_both(lines)?;
-            if frame.width() == 0 || frame.height() == 0 {
-                break;
-            }
-            self.check_frame(&frame)?;
-            self.frames.push(frame);
+        let size = (frame.width(), frame.height());
+        if lines.options().lenient && self.width != 0 && size != (self.width, self.height) {
+            frame.resize(self.width, self.height, lines.options().pad_with);
+            frame.recalc_colors();
+            let message = format!(
+                "resized frame from {}x{} to {}x{}",
+                size.0, size.1, self.width, self.height
+            );
+            let error = if size.0 != self.width {
+                Error::WidthMismatch
+            } else {
+                Error::HeightMismatch
+            };
+            lines.fix_at(span.clone(), Some(error), message);
         }
-        self.merge()
+        self.check_frame(frame).map_err(|err| err.locate(span))
     }
 
-    pub(crate) fn read_body_text<R: Read>(
+    fn read_body_frames<I: Iterator<Item = io::Result<String>>>(
         &mut self,
-        lines: &mut io::Lines<BufReader<R>>,
+        lines: &mut Lines<I>,
+        header: &Header,
     ) -> Result<()> {
-        loop {
-            let frame = Frame::read_text(lines)?;
-            if frame.width() == 0 || frame.height() == 0 {
-                break;
-            }
-            self.check_frame(&frame)?;
+        while let Some(frame) = self.read_body_frame(lines, header, self.frames.len())? {
             self.frames.push(frame);
         }
         self.merge()
     }
 
-    pub(crate) fn read_body_color<R: Read>(
+    /// Reads the next frame of the body, numbered `index`, without applying pins.
+    /// Returns `None` at the end of the body.
+    pub(crate) fn read_body_frame<I: Iterator<Item = io::Result<String>>>(
         &mut self,
-        lines: &mut io::Lines<BufReader<R>>,
-    ) -> Result<()> {
-        loop {
-            let frame = Frame::read_color(lines)?;
-            if frame.width() == 0 || frame.height() == 0 {
-                break;
-            }
-            self.check_frame(&frame)?;
-            self.frames.push(frame);
+        lines: &mut Lines<I>,
+        header: &Header,
+        index: usize,
+    ) -> Result<Option<Frame>> {
+        let span = Span {
+            line: lines.line() + 1,
+            block: Some("body".into()),
+            frame: Some(index),
+            ..Span::default()
+        };
+        let fixes = lines.fixes().len();
+        let frame = if !header.get_colors() || self.color_pin != None {
+            Frame::read_text_lines(lines)
+        } else if self.text_pin != None {
+            Frame::read_color_lines(lines)
+        } else {
+            Frame::read_both_lines(lines)
+        };
+        let mut frame = frame.map_err(|err| err.locate(span.clone()))?;
+        if frame.width() == 0 || frame.height() == 0 {
+            return Ok(None);
         }
-        self.merge()
+        self.fit_frame(&mut frame, lines, span.clone())?;
+        lines.locate_fixes(fixes, &span);
+        Ok(Some(frame))
     }
 
-    pub(crate) fn read_legacy<R: Read>(
+    /// Reads a legacy body. Background colors are mapped to palette entries
+    /// added to `header` as needed, and comments are kept as its trailing comments.
+    /// In lenient mode, comments found after the first frame row are reported
+    /// as fixes, as they no longer appear next to their frame.
+    pub(crate) fn read_legacy<I: Iterator<Item = io::Result<String>>>(
         info: LegacyHeaderInfo,
-        lines: &mut io::Lines<BufReader<R>>,
+        lines: &mut Lines<I>,
+        header: &mut Header,
     ) -> Result<Self> {
         let mut frames = Self {
             width: info.width,
@@ -1374,11 +1748,29 @@ impl Frames {
         let mut bg_len: usize = 0;
         let mut mode = LegacyScanMode::Text;
 
-        for line in lines {
+        while let Some(line) = lines.next() {
             let mut comment = false;
             let line = line?;
             let line = match line.split_once("\t") {
-                Some((a, _)) => {
+                Some((a, b)) => {
+                    let comment = normalize_text(b).trim().to_string();
+                    let in_body = !frames.frames.is_empty()
+                        || !frame.rows.is_empty()
+                        || !row.is_empty()
+                        || !a.trim().is_empty();
+                    if !comment.is_empty() && in_body && lines.options().lenient {
+                        let span = Span {
+                            line: lines.line(),
+                            column: Some(a.chars().count() + 1),
+                            frame: Some(frames.frames.len()),
+                            ..Span::default()
+                        };
+                        let message = format!("moved comment '{}' to the header", comment);
+                        lines.fix_at(span, None, message);
+                    }
+                    if !comment.is_empty() {
+                        header.trailing_comments.push(comment);
+                    }
                     if a.is_empty() {
                         continue;
                     }
@@ -1426,6 +1818,15 @@ impl Frames {
                         }
                     }
                     LegacyScanMode::Bg => {
+                        let cell = &mut row[bg_len];
+                        let pair = ColorPair {
+                            fg: cell.to_pair(&header.palette).fg,
+                            bg: Color::from_char_builtin(Char::new_must(trans_color(c))),
+                        };
+                        if cell.color.is_none() {
+                            frame.color += 1;
+                        }
+                        cell.color = Some(header.palette.name_color(pair)?);
                         bg_len += 1;
                         if bg_len == info.width {
                             mode = mode.next(info.colors);
@@ -1520,6 +1921,69 @@ impl Frames {
             self.fmt_body_text(f)
         }
     }
+
+    /// Formats with optional color inclusion, keeping the given pin layout
+    /// while the frames allow it and choosing one as `fmt_with_color` otherwise.
+    pub(crate) fn fmt_with_layout(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        color: bool,
+        text_pin: bool,
+        color_pin: bool,
+    ) -> std::fmt::Result {
+        let (text_pinned, colors_pinned) = self.pinned();
+        if !color {
+            self.fmt_body_text(f)
+        } else if color_pin && colors_pinned {
+            self.fmt_pinned_colors(f)?;
+            self.fmt_body_text(f)
+        } else if text_pin && text_pinned {
+            self.fmt_pinned_text(f)?;
+            self.fmt_body_colors(f)
+        } else if !text_pin && !color_pin {
+            self.fmt_body_both(f)
+        } else {
+            self.fmt_with_color(f, color)
+        }
+    }
+}
+
+/// Serialized form of frames.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct FramesData {
+    width: usize,
+    height: usize,
+    frames: Vec<Frame>,
+}
+
+#[cfg(feature = "serde")]
+impl From<Frames> for FramesData {
+    fn from(frames: Frames) -> Self {
+        Self {
+            width: frames.width,
+            height: frames.height,
+            frames: frames.frames,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl std::convert::TryFrom<FramesData> for Frames {
+    type Error = Error;
+    fn try_from(data: FramesData) -> Result<Self> {
+        let mut frames = Frames::new(0, data.width, data.height, Cell::default());
+        for frame in data.frames {
+            if frame.width() != data.width {
+                return Err(Error::WidthMismatch);
+            }
+            if frame.height() != data.height {
+                return Err(Error::HeightMismatch);
+            }
+            frames.frames.push(frame);
+        }
+        Ok(frames)
+    }
 }
 
 /// Displays the frames using optimal format (with pins if possible).
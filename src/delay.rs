@@ -6,6 +6,7 @@ use crate::error::{Error, Result};
 /// Frame delay configuration for animations.
 /// Contains a global delay and optional per-frame overrides.
 #[derive(Default, Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Delay {
     /// Global delay in milliseconds, applied to all frames unless overridden.
     /// A value of 0 is interpreted as the default (50ms).
@@ -72,6 +73,87 @@ impl Delay {
         }
         delays
     }
+    /// Builds a delay map from a vector of per-frame delays.
+    /// The most common delay becomes the global one; zero entries fall back to it.
+    pub fn from_vec_delays(delays: &[usize]) -> Self {
+        let mut counts = HashMap::<usize, usize>::new();
+        for d in delays.iter().filter(|d| **d > 0) {
+            *counts.entry(*d).or_insert(0) += 1;
+        }
+        let global = counts
+            .iter()
+            .max_by_key(|(d, n)| (**n, std::cmp::Reverse(**d)))
+            .map(|(d, _)| *d)
+            .unwrap_or(50);
+        let mut per_frame = HashMap::new();
+        for (frame, delay) in delays.iter().enumerate() {
+            if *delay > 0 && *delay != global {
+                per_frame.insert(frame, *delay);
+            }
+        }
+        Self { global, per_frame }
+    }
+    /// Applies a change of frame order to the per-frame delays.
+    /// `f` edits the effective delays of all `frames` frames; the result is
+    /// stored as overrides of the unchanged global delay.
+    pub(crate) fn map_frames<F: FnOnce(&mut Vec<usize>)>(&mut self, frames: usize, f: F) {
+        let mut delays = self.to_vec_delays(frames);
+        f(&mut delays);
+        let global = self.get_global();
+        self.per_frame = delays
+            .into_iter()
+            .enumerate()
+            .filter(|(_, delay)| *delay != global)
+            .collect();
+    }
+    /// Multiplies the global and per-frame delays by `factor`, rounding to
+    /// whole milliseconds of at least 1ms.
+    /// Fails if `factor` is not a positive finite number.
+    pub fn scale(&mut self, factor: f64) -> Result<()> {
+        if !factor.is_finite() || factor <= 0.0 {
+            return Err(Error::DelayScale(factor));
+        }
+        self.retime(|delay| ((delay as f64 * factor).round() as usize).max(1));
+        Ok(())
+    }
+    /// Clamps the global and per-frame delays into `min..=max`; the lower
+    /// bound is at least 1ms.
+    pub fn clamp(&mut self, min: usize, max: Option<usize>) {
+        self.retime(|delay| clamp_delay(delay, min, max));
+    }
+    /// Maps every effective delay, dropping overrides equal to the new global.
+    fn retime<F: Fn(usize) -> usize>(&mut self, f: F) {
+        let global = f(self.get_global());
+        self.per_frame = self
+            .per_frame
+            .keys()
+            .map(|frame| (*frame, f(self.get_frame(*frame))))
+            .filter(|(_, delay)| *delay != global)
+            .collect();
+        self.global = global;
+    }
+}
+
+/// Converts absolute timestamps into the delays between them.
+/// The last entry is 0, as its duration is unknown.
+pub(crate) fn delays_from_timestamps(timestamps: &[usize]) -> Vec<usize> {
+    let mut delays: Vec<usize> = timestamps
+        .windows(2)
+        .map(|w| w[1].saturating_sub(w[0]))
+        .collect();
+    if !timestamps.is_empty() {
+        delays.push(0);
+    }
+    delays
+}
+
+/// Clamps a delay into `min..=max`; the lower bound is at least 1ms.
+pub(crate) fn clamp_delay(delay: usize, min: usize, max: Option<usize>) -> usize {
+    let delay = delay.max(min).max(1);
+    match max {
+        Some(max) => delay.min(max.max(1)),
+        None => delay,
+    }
 }
 
 /// Formats the delay as a string: global value followed by space-separated
@@ -0,0 +1,322 @@
+use core::fmt;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use ordermap::OrderMap;
+
+use crate::art::Art;
+use crate::chars::normalize_text;
+use crate::error::{Error, Result};
+use crate::helpers::Fmt;
+use crate::lines::Lines;
+
+/// Identity of a piece of 3a source that maps to one part of the model.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Key {
+    /// The `@3a` line.
+    Start,
+    /// A header key line, by key (and author or color name).
+    Field(String),
+    /// Comment lines before a header key line.
+    Comments(String),
+    /// Trailing header comments and the empty line ending the header.
+    End,
+    /// The `@attach` block.
+    Attach,
+    /// Extra blocks with the given title.
+    Block(String),
+    /// Frame blocks: `@text-pin`, `@color-pin` and `@body`.
+    Frames,
+    /// Whole legacy file.
+    Legacy,
+}
+
+impl Key {
+    fn in_header(&self) -> bool {
+        matches!(
+            self,
+            Key::Start | Key::Field(_) | Key::Comments(_) | Key::End
+        )
+    }
+}
+
+/// Art together with the exact source it was parsed from.
+///
+/// Writing a document that was not edited reproduces the source byte for
+/// byte. After edits, the source is rewritten in units: every header key and
+/// its comments, the trailing header comments, the attached content, each
+/// extra block title and the frames are compared separately, and only the
+/// units that changed are rewritten. The frames are a single unit, so
+/// changing any frame rewrites the pin and body blocks whole, keeping the
+/// pins only while the frames allow it. New header keys are added at the
+/// end of the header and new blocks at the end of the file.
+///
+/// Legacy files are kept as they were until edited, then written whole in
+/// the modern format.
+#[derive(Debug, Clone)]
+pub struct Document {
+    art: Art,
+    original: Art,
+    source: String,
+    units: Vec<(Key, String)>,
+    text_pin: bool,
+    color_pin: bool,
+}
+
+impl Document {
+    /// Reads a document from any reader.
+    pub fn from_reader<R: Read>(mut r: R) -> Result<Self> {
+        let mut source = String::new();
+        r.read_to_string(&mut source)
+            .map_err(|err| match err.kind() {
+                std::io::ErrorKind::InvalidData => Error::NotUtf8,
+                _ => err.into(),
+            })?;
+        source.parse()
+    }
+
+    /// Reads a document from a file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_reader(File::open(path)?)
+    }
+
+    /// Writes the document to a file.
+    pub fn to_file<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let mut file = File::create(path)?;
+        write!(file, "{}", self)
+    }
+
+    /// Returns the art.
+    pub fn art(&self) -> &Art {
+        &self.art
+    }
+
+    /// Returns the art for editing.
+    pub fn art_mut(&mut self) -> &mut Art {
+        &mut self.art
+    }
+
+    /// Consumes the document and returns the art.
+    pub fn into_art(self) -> Art {
+        self.art
+    }
+
+    /// Returns the source the document was parsed from.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Returns whether the art differs from the parsed source.
+    pub fn is_modified(&self) -> bool {
+        self.art.to_string() != self.original.to_string()
+    }
+
+    /// Renders the parts of an art that source units are compared by.
+    fn canonical(&self, art: &Art) -> OrderMap<Key, String> {
+        let mut units = OrderMap::new();
+        let color = art.color();
+        let header = Fmt(|f| art.header.fmt_with_colors(f, color)).to_string();
+        for (key, text) in split_header(header.split_inclusive('\n')) {
+            units.entry(key).or_insert_with(String::new).push_str(&text);
+        }
+        if let Some(attached) = &art.attached {
+            if !attached.is_empty() {
+                units.insert(Key::Attach, format!("@attach\n{}\n\n", attached));
+            }
+        }
+        for block in &art.extra {
+            units
+                .entry(Key::Block(block.title.clone()))
+                .or_insert_with(String::new)
+                .push_str(&block.to_string());
+        }
+        let (text_pin, color_pin) = (self.text_pin, self.color_pin);
+        let frames = Fmt(|f| art.frames.fmt_with_layout(f, color, text_pin, color_pin));
+        units.insert(Key::Frames, frames.to_string());
+        units
+    }
+}
+
+impl FromStr for Document {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        let mut blocks = Vec::new();
+        let art = Art::read_lines_with_blocks(&mut Lines::from_reader(s.as_bytes()), &mut blocks)?;
+        let lines: Vec<&str> = s.split_inclusive('\n').collect();
+        let mut units = Vec::new();
+        if art.header.legacy.is_some() {
+            units.push((Key::Legacy, s.to_string()));
+        } else {
+            let header_end = blocks.first().map_or(lines.len(), |(_, line)| line - 1);
+            units = split_header(lines[..header_end].iter().copied());
+            for (i, (title, line)) in blocks.iter().enumerate() {
+                let end = blocks.get(i + 1).map_or(lines.len(), |(_, next)| next - 1);
+                let key = match title.as_str() {
+                    "attach" => Key::Attach,
+                    "text-pin" | "color-pin" | "body" => Key::Frames,
+                    title => Key::Block(title.into()),
+                };
+                units.push((key, lines[line - 1..end].concat()));
+            }
+        }
+        let has_block = |name: &str| blocks.iter().any(|(title, _)| title == name);
+        Ok(Self {
+            original: art.clone(),
+            art,
+            source: s.into(),
+            units,
+            text_pin: has_block("text-pin"),
+            color_pin: has_block("color-pin"),
+        })
+    }
+}
+
+/// Writes the source, with the units changed by edits rewritten.
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !self.is_modified() {
+            return f.write_str(&self.source);
+        }
+        if self.original.header.legacy.is_some() {
+            return self.art.fmt(f);
+        }
+        let before = self.canonical(&self.original);
+        let after = self.canonical(&self.art);
+        let changed = |key: &Key| before.get(key) != after.get(key);
+        let added = |header: bool| {
+            after.iter().filter(move |(key, _)| {
+                key.in_header() == header
+                    && changed(key)
+                    && !self.units.iter().any(|(unit, _)| unit == *key)
+            })
+        };
+        let mut written = HashSet::new();
+        for (key, text) in &self.units {
+            if *key == Key::End {
+                for (_, text) in added(true) {
+                    f.write_str(text)?;
+                }
+            }
+            if !changed(key) {
+                f.write_str(text)?;
+            } else if written.insert(key) {
+                if let Some(text) = after.get(key) {
+                    f.write_str(text)?;
+                }
+            }
+        }
+        for (_, text) in added(false) {
+            f.write_str(text)?;
+        }
+        Ok(())
+    }
+}
+
+/// Splits header lines into units. Comment lines form a unit of the key
+/// line after them, and everything from the empty line ending the header
+/// on belongs to its end.
+fn split_header<'a>(lines: impl IntoIterator<Item = &'a str>) -> Vec<(Key, String)> {
+    let mut units = Vec::new();
+    let mut comments = String::new();
+    let mut end: Option<String> = None;
+    for (n, line) in lines.into_iter().enumerate() {
+        if let Some(end) = &mut end {
+            end.push_str(line);
+            continue;
+        }
+        let normalized = normalize_text(line.trim_end_matches(['\n', '\r']));
+        if normalized.is_empty() {
+            end = Some(std::mem::take(&mut comments) + line);
+            continue;
+        }
+        if normalized.starts_with(";;") {
+            comments.push_str(line);
+            continue;
+        }
+        let field = if n == 0 && normalized == "@3a" {
+            units.push((Key::Start, line.to_string()));
+            continue;
+        } else if normalized.starts_with('#') {
+            "#".to_string()
+        } else {
+            let (key, values) = normalized.split_once(' ').unwrap_or((&normalized, ""));
+            let (key, values) = (key.trim(), values.trim());
+            match key {
+                "author" | "orig-author" => format!("{} {}", key, values),
+                "col" => format!("col {}", values.split(' ').next().unwrap_or_default()),
+                key => key.to_string(),
+            }
+        };
+        if !comments.is_empty() {
+            units.push((Key::Comments(field.clone()), std::mem::take(&mut comments)));
+        }
+        units.push((Key::Field(field), line.to_string()));
+    }
+    units.push((Key::End, end.unwrap_or(comments)));
+    units
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content::Cell;
+
+    const SOURCE: &str = "@3a\n;; spaced comment\ntitle  Demo\nloop yes\nfuture-key kept\ncol x fg:red\n\n@notes\nfirst\n\n@color-pin\nxx\n\n@body\nab\n\ncd\n\n";
+
+    #[test]
+    fn unedited_round_trip() {
+        let doc: Document = SOURCE.parse().unwrap();
+        assert!(!doc.is_modified());
+        assert_eq!(doc.to_string(), SOURCE);
+        let dna = include_str!("../examples/dna.3a");
+        assert_eq!(dna.parse::<Document>().unwrap().to_string(), dna);
+    }
+
+    #[test]
+    fn edits_touch_only_changed_units() {
+        let mut doc: Document = SOURCE.parse().unwrap();
+        doc.art_mut().header.title = Some("Renamed".into());
+        assert_eq!(
+            doc.to_string(),
+            SOURCE.replace("title  Demo", "title Renamed")
+        );
+
+        let mut doc: Document = SOURCE.parse().unwrap();
+        let cell = doc.art().get(1, 0, 0, Cell::default());
+        doc.art_mut().set(
+            1,
+            0,
+            0,
+            Cell {
+                text: crate::chars::Char::new_must('e'),
+                ..cell
+            },
+        );
+        assert_eq!(doc.to_string(), SOURCE.replace("cd\n", "ed\n"));
+
+        let mut doc: Document = SOURCE.parse().unwrap();
+        doc.art_mut().header.license = Some("CC0-1.0".into());
+        assert_eq!(
+            doc.to_string(),
+            SOURCE.replace("col x fg:red\n\n", "col x fg:red\nlicense CC0-1.0\n\n")
+        );
+    }
+
+    #[test]
+    fn reader_errors() {
+        struct Broken;
+        impl Read for Broken {
+            fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::new(std::io::ErrorKind::Other, "broken"))
+            }
+        }
+        assert!(matches!(Document::from_reader(Broken), Err(Error::Io(_))));
+        assert!(matches!(
+            Document::from_reader(&[0xff][..]),
+            Err(Error::NotUtf8)
+        ));
+    }
+}
@@ -4,6 +4,18 @@ use std::{num::ParseIntError, sync::Arc};
 pub type Result<T> = std::result::Result<T, Error>;
 
 /// Errors that can occur when parsing or processing 3a format.
+///
+/// Errors found while reading 3a source are returned wrapped in
+/// [`Error::At`] with their location. Code matching on the kind of such an
+/// error has to match on [`Error::inner`] instead of the error itself:
+///
+/// ```
+/// use rs3a::{Art, Error};
+///
+/// let err = "@3a\n\n@body\nab\nabc\n\n".parse::<Art>().unwrap_err();
+/// assert!(matches!(err, Error::At(..)));
+/// assert!(matches!(err.inner(), Error::WidthMismatch));
+/// ```
 #[derive(Debug, Clone)]
 pub enum Error {
     /// Failed to parse delay line.
@@ -34,6 +46,8 @@ pub enum Error {
 
     /// Failed to parse preview value.
     PreviewParsing(String, ParseIntError),
+    /// Failed to parse loop count value.
+    LoopCountParsing(String, ParseIntError),
 
     /// Invalid color name.
     ColorName(String),
@@ -59,6 +73,19 @@ pub enum Error {
     /// Expected block title but got something else.
     BlockExpected(String),
 
+    /// Pin block found after the body while streaming frames.
+    PinAfterBody(String),
+
+    /// Palette entry with a color outside of the legacy 16 color scheme.
+    LegacyColor(String),
+    /// Per-frame delays, which legacy format has no syntax for.
+    LegacyDelay,
+
+    /// Frame rate that is not a positive finite number.
+    FrameRate(f64),
+    /// Delay scale factor that is not a positive finite number.
+    DelayScale(f64),
+
     /// Character with disallowed code point.
     DisallowedChar(u32),
     /// Failed to convert string to single character (invalid length).
@@ -72,8 +99,91 @@ pub enum Error {
 
     TtyRecInvalidLength,
 
+    /// Malformed JSON document.
+    JsonParsing(String),
+    /// Malformed asciicast recording.
+    AsciicastParsing(String),
+    /// Malformed PSF or BDF bitmap font.
+    FontParsing(String),
+
     /// I/O error occurred.
     Io(Arc<std::io::Error>),
+
+    /// Error with its location in the parsed source. Every error found by
+    /// the 3a parsers is wrapped in it; see [`Error::inner`].
+    At(Span, Box<Error>),
+}
+
+/// Location of a parse error in the source.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Span {
+    /// 1-based line number.
+    pub line: usize,
+    /// 1-based column in characters, if known.
+    pub column: Option<usize>,
+    /// Title of the block being read, if any.
+    pub block: Option<String>,
+    /// Index of the frame being read, if any.
+    pub frame: Option<usize>,
+}
+
+impl Span {
+    /// Fills unknown parts from a less specific location.
+    pub(crate) fn fill(&mut self, span: &Span) {
+        if self.line == 0 {
+            self.line = span.line;
+        }
+        self.column = self.column.or(span.column);
+        if self.block.is_none() {
+            self.block = span.block.clone();
+        }
+        self.frame = self.frame.or(span.frame);
+    }
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}", self.line)?;
+        if let Some(column) = self.column {
+            write!(f, ", column {}", column)?;
+        }
+        match (&self.block, self.frame) {
+            (Some(block), Some(frame)) => write!(f, " (block {}, frame {})", block, frame),
+            (Some(block), None) => write!(f, " (block {})", block),
+            (None, Some(frame)) => write!(f, " (frame {})", frame),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+impl Error {
+    /// Returns the location of the error in the parsed source, if known.
+    pub fn span(&self) -> Option<&Span> {
+        match self {
+            Error::At(span, _) => Some(span),
+            _ => None,
+        }
+    }
+
+    /// Returns the error without its location.
+    pub fn inner(&self) -> &Error {
+        match self {
+            Error::At(_, err) => err.inner(),
+            err => err,
+        }
+    }
+
+    /// Attaches a location to the error.
+    /// Parts already known from a more specific location are kept.
+    pub(crate) fn locate(self, span: Span) -> Self {
+        match self {
+            Error::At(mut known, err) => {
+                known.fill(&span);
+                Error::At(known, err)
+            }
+            err => Error::At(span, Box::new(err)),
+        }
+    }
 }
 
 impl From<std::io::Error> for Error {
@@ -118,6 +228,9 @@ impl Display for Error {
             Error::PreviewParsing(v, err) => {
                 write!(f, "failed to parse preview value '{}': {}", v, err,)
             }
+            Error::LoopCountParsing(v, err) => {
+                write!(f, "failed to parse loop count value '{}': {}", v, err)
+            }
             Error::ColorName(name) => write!(f, "'{}' cannot be used as color name", name),
             Error::ColorMapDup(name) => write!(f, "color mapping for '{}' duplicates", name),
             Error::WidthMismatch => {
@@ -128,6 +241,20 @@ impl Display for Error {
             }
             Error::BlockDup(name) => write!(f, "block {} duplicated", name),
             Error::BlockExpected(line) => write!(f, "block title expected, got: {}", line),
+            Error::LegacyColor(name) => {
+                write!(f, "color {} can not be expressed in legacy 3a format", name)
+            }
+            Error::FrameRate(fps) => write!(f, "invalid frame rate {}", fps),
+            Error::DelayScale(factor) => write!(f, "invalid delay scale factor {}", factor),
+            Error::LegacyDelay => {
+                write!(
+                    f,
+                    "per-frame delays can not be expressed in legacy 3a format"
+                )
+            }
+            Error::PinAfterBody(name) => {
+                write!(f, "block {} must precede the body to stream frames", name)
+            }
             Error::FramesMismatch => write!(f, "channels frame count mismatch"),
             Error::ColorsMismatch => write!(f, "color info from header and body mismatch"),
             Error::VoidTextChannel => write!(f, "0 frames in text channel"),
@@ -137,8 +264,12 @@ impl Display for Error {
             }
             Error::UnexpectedEof => write!(f, "unexpected EOF"),
             Error::TtyRecInvalidLength => write!(f, "invalid ttyrec len"),
+            Error::JsonParsing(msg) => write!(f, "failed to parse json: {}", msg),
+            Error::AsciicastParsing(msg) => write!(f, "failed to parse asciicast: {}", msg),
+            Error::FontParsing(msg) => write!(f, "failed to parse bitmap font: {}", msg),
             Error::DelayOverflow => write!(f, "too big delay"),
             Error::NotUtf8 => write!(f, "failed to parse text as utf-8"),
+            Error::At(span, err) => write!(f, "{}: {}", span, err),
         }
     }
 }
@@ -0,0 +1,340 @@
+use std::collections::HashMap;
+
+use crate::raster::{shared_palette, Canvas, Rgb};
+
+/// Largest code width allowed by GIF LZW.
+const MAX_CODE_SIZE: u8 = 12;
+
+/// Converts frame delays in milliseconds to GIF centiseconds.
+/// Rounding is done on the accumulated time so the total duration is kept.
+/// Delays below 2cs are raised to 2cs, as most viewers slow down shorter ones.
+pub(crate) fn delays_to_centiseconds(delays: &[usize]) -> Vec<u16> {
+    let mut time = 0;
+    let mut shown = 0;
+    let mut out = Vec::with_capacity(delays.len());
+    for delay in delays {
+        time += delay;
+        let end = (time + 5) / 10;
+        let cs = end.saturating_sub(shown).max(2);
+        shown += cs;
+        out.push(cs.min(u16::MAX as usize) as u16);
+    }
+    out
+}
+
+/// Encodes frames as a GIF89a animation.
+/// A global color table is used when all frames fit into 256 colors,
+/// otherwise each frame gets a local table reduced to 256 colors.
+/// `plays` is the number of times the animation is played, `None` for forever.
+pub(crate) fn encode(frames: &[Canvas], delays: &[u16], plays: Option<usize>) -> Vec<u8> {
+    let width = frames.first().map(|f| f.width).unwrap_or(0);
+    let height = frames.first().map(|f| f.height).unwrap_or(0);
+    let global = shared_palette(frames);
+
+    let mut out = Vec::new();
+    out.extend_from_slice(b"GIF89a");
+    push_u16(&mut out, width);
+    push_u16(&mut out, height);
+    match &global {
+        Some((table, _)) => {
+            let bits = table_bits(table.len());
+            out.push(0xF0 | (bits - 1));
+            out.push(0); // background color index
+            out.push(0); // pixel aspect ratio
+            push_table(&mut out, table, bits);
+        }
+        None => out.extend_from_slice(&[0, 0, 0]),
+    }
+    // The NETSCAPE extension counts repeats after the first play, 0 is forever.
+    let repeats = match plays {
+        None => Some(0),
+        Some(plays) if plays > 1 => Some(plays - 1),
+        Some(_) => None,
+    };
+    if let Some(repeats) = repeats {
+        out.extend_from_slice(b"\x21\xFF\x0BNETSCAPE2.0\x03\x01");
+        push_u16(&mut out, repeats);
+        out.push(0);
+    }
+    for (f, frame) in frames.iter().enumerate() {
+        // Graphic control extension: keep previous frame, no transparency.
+        out.extend_from_slice(&[0x21, 0xF9, 0x04, 0x04]);
+        push_u16(&mut out, delays.get(f).copied().unwrap_or(0) as usize);
+        out.extend_from_slice(&[0, 0]);
+
+        out.push(0x2C);
+        push_u16(&mut out, 0);
+        push_u16(&mut out, 0);
+        push_u16(&mut out, frame.width);
+        push_u16(&mut out, frame.height);
+        let (indices, bits) = match &global {
+            Some((table, index)) => {
+                out.push(0);
+                let indices = frame.pixels.iter().map(|px| index[px]).collect();
+                (indices, table_bits(table.len()))
+            }
+            None => {
+                let (table, indices) = local_palette(frame);
+                let bits = table_bits(table.len());
+                out.push(0x80 | (bits - 1));
+                push_table(&mut out, &table, bits);
+                (indices, bits)
+            }
+        };
+        let min_code_size = bits.max(2);
+        out.push(min_code_size);
+        for chunk in lzw_encode(&indices, min_code_size).chunks(255) {
+            out.push(chunk.len() as u8);
+            out.extend_from_slice(chunk);
+        }
+        out.push(0);
+    }
+    out.push(0x3B);
+    out
+}
+
+fn push_u16(out: &mut Vec<u8>, value: usize) {
+    out.extend_from_slice(&(value.min(u16::MAX as usize) as u16).to_le_bytes());
+}
+
+/// Returns the number of bits needed to index a color table of `len` entries.
+fn table_bits(len: usize) -> u8 {
+    let mut bits = 1;
+    while (1 << bits) < len {
+        bits += 1;
+    }
+    bits
+}
+
+/// Writes a color table padded to `2^bits` entries.
+fn push_table(out: &mut Vec<u8>, table: &[Rgb], bits: u8) {
+    for i in 0..(1 << bits) {
+        let (r, g, b) = table.get(i).copied().unwrap_or((0, 0, 0));
+        out.extend_from_slice(&[r, g, b]);
+    }
+}
+
+/// Builds a color table of at most 256 most frequent frame colors,
+/// mapping the rest to the nearest kept color.
+fn local_palette(frame: &Canvas) -> (Vec<Rgb>, Vec<u8>) {
+    let mut counts: HashMap<Rgb, usize> = HashMap::new();
+    for px in &frame.pixels {
+        *counts.entry(*px).or_insert(0) += 1;
+    }
+    let mut colors: Vec<(Rgb, usize)> = counts.into_iter().collect();
+    colors.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    let table: Vec<Rgb> = colors.iter().take(256).map(|c| c.0).collect();
+    let mut index: HashMap<Rgb, u8> = table
+        .iter()
+        .enumerate()
+        .map(|(i, c)| (*c, i as u8))
+        .collect();
+    let mut indices = Vec::with_capacity(frame.pixels.len());
+    for px in &frame.pixels {
+        let i = match index.get(px) {
+            Some(i) => *i,
+            None => {
+                let i = nearest(&table, *px);
+                index.insert(*px, i);
+                i
+            }
+        };
+        indices.push(i);
+    }
+    (table, indices)
+}
+
+fn nearest(table: &[Rgb], color: Rgb) -> u8 {
+    let dist = |c: &Rgb| {
+        let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+        d(c.0, color.0) + d(c.1, color.1) + d(c.2, color.2)
+    };
+    (0..table.len())
+        .min_by_key(|i| dist(&table[*i]))
+        .unwrap_or(0) as u8
+}
+
+/// Packs variable width codes into bytes, least significant bit first.
+struct BitWriter {
+    out: Vec<u8>,
+    acc: u32,
+    bits: u8,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u8) {
+        self.acc |= (code as u32) << self.bits;
+        self.bits += size;
+        while self.bits >= 8 {
+            self.out.push(self.acc as u8);
+            self.acc >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.out.push(self.acc as u8);
+        }
+        self.out
+    }
+}
+
+/// Compresses color indices with GIF flavoured variable width LZW.
+pub(crate) fn lzw_encode(indices: &[u8], min_code_size: u8) -> Vec<u8> {
+    let clear: u16 = 1 << min_code_size;
+    let eoi = clear + 1;
+    let mut dict: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next = eoi + 1;
+    let mut size = min_code_size + 1;
+    let mut writer = BitWriter {
+        out: Vec::new(),
+        acc: 0,
+        bits: 0,
+    };
+    writer.write(clear, size);
+    let mut prefix: Option<u16> = None;
+    for &k in indices {
+        let p = match prefix {
+            Some(p) => p,
+            None => {
+                prefix = Some(k as u16);
+                continue;
+            }
+        };
+        if let Some(code) = dict.get(&(p, k)) {
+            prefix = Some(*code);
+            continue;
+        }
+        writer.write(p, size);
+        if next < (1 << MAX_CODE_SIZE) {
+            dict.insert((p, k), next);
+            next += 1;
+            if next > (1 << size) && size < MAX_CODE_SIZE {
+                size += 1;
+            }
+        } else {
+            writer.write(clear, size);
+            dict.clear();
+            next = eoi + 1;
+            size = min_code_size + 1;
+        }
+        prefix = Some(k as u16);
+    }
+    if let Some(p) = prefix {
+        writer.write(p, size);
+    }
+    writer.write(eoi, size);
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reference GIF LZW decoder.
+    fn lzw_decode(data: &[u8], min_code_size: u8) -> Vec<u8> {
+        let clear = 1usize << min_code_size;
+        let eoi = clear + 1;
+        let mut table: Vec<Vec<u8>> = Vec::new();
+        let mut size = min_code_size + 1;
+        let mut prev: Option<Vec<u8>> = None;
+        let mut out = Vec::new();
+        let (mut acc, mut bits, mut pos) = (0u32, 0u8, 0);
+        loop {
+            while bits < size {
+                acc |= (data[pos] as u32) << bits;
+                pos += 1;
+                bits += 8;
+            }
+            let code = (acc & ((1 << size) - 1)) as usize;
+            acc >>= size;
+            bits -= size;
+            if code == clear {
+                table = (0..clear).map(|i| vec![i as u8]).collect();
+                table.push(vec![]);
+                table.push(vec![]);
+                size = min_code_size + 1;
+                prev = None;
+                continue;
+            }
+            if code == eoi {
+                return out;
+            }
+            let entry = match (table.get(code), &prev) {
+                (Some(e), _) => e.clone(),
+                (None, Some(p)) => {
+                    let mut e = p.clone();
+                    e.push(p[0]);
+                    e
+                }
+                (None, None) => panic!("invalid code"),
+            };
+            out.extend_from_slice(&entry);
+            if let Some(mut p) = prev {
+                p.push(entry[0]);
+                table.push(p);
+                if table.len() == (1 << size) && size < MAX_CODE_SIZE {
+                    size += 1;
+                }
+            }
+            prev = Some(entry);
+        }
+    }
+
+    #[test]
+    fn lzw_round_trip() {
+        let mut data: Vec<u8> = (0..20000u32)
+            .map(|i| ((i * 7) % 13 + i / 997) as u8 % 16)
+            .collect();
+        data.extend(std::iter::repeat(3).take(5000));
+        let encoded = lzw_encode(&data, 4);
+        assert_eq!(lzw_decode(&encoded, 4), data);
+        // Noisy input fills the dictionary and forces clear codes.
+        let mut seed: u32 = 1;
+        let noise: Vec<u8> = (0..40000)
+            .map(|_| {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                (seed >> 16) as u8
+            })
+            .collect();
+        assert_eq!(lzw_decode(&lzw_encode(&noise, 8), 8), noise);
+        assert_eq!(
+            lzw_decode(&lzw_encode(&[1, 1, 1, 0], 2), 2),
+            vec![1, 1, 1, 0]
+        );
+    }
+
+    #[test]
+    fn centiseconds_keep_total() {
+        assert_eq!(delays_to_centiseconds(&[33, 33, 34]), vec![3, 4, 3]);
+        assert_eq!(delays_to_centiseconds(&[5, 100]), vec![2, 9]);
+    }
+
+    #[test]
+    fn local_tables_for_many_colors() {
+        let mut frame = Canvas::new(300, 1, (0, 0, 0));
+        for (i, px) in frame.pixels.iter_mut().enumerate() {
+            *px = (i as u8, (i / 256) as u8, 0);
+        }
+        let gif = encode(&[frame], &[10], Some(1));
+        assert_eq!(&gif[..6], b"GIF89a");
+        // No global color table.
+        assert_eq!(gif[10] & 0x80, 0);
+        assert_eq!(*gif.last().unwrap(), 0x3B);
+    }
+
+    #[test]
+    fn netscape_repeats() {
+        let repeats = |plays| {
+            let gif = encode(&[Canvas::new(1, 1, (0, 0, 0))], &[10], plays);
+            let ext = b"NETSCAPE2.0\x03\x01";
+            gif.windows(ext.len())
+                .position(|w| w == ext)
+                .map(|i| u16::from_le_bytes([gif[i + ext.len()], gif[i + ext.len() + 1]]))
+        };
+        assert_eq!(repeats(None), Some(0));
+        assert_eq!(repeats(Some(1)), None);
+        assert_eq!(repeats(Some(3)), Some(2));
+    }
+}
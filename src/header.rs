@@ -9,15 +9,16 @@ use std::{
 
 use ordermap::{OrderMap, OrderSet};
 
-use crate::error::{Error, Result};
+use crate::error::{Error, Result, Span};
 use crate::{
     chars::{normalize_text, Char},
     comments::Comments,
 };
-use crate::{delay::Delay, ColorPair, Palette};
+use crate::{delay::Delay, lines::Lines, ColorPair, Palette};
 
 /// Represents the header of a 3a file.
 #[derive(Default, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Header {
     /// Optional title of the artwork.
     pub title: Option<String>,
@@ -55,6 +56,13 @@ pub struct Header {
     /// Comments associated with the loop flag.
     pub loop_comments: Comments,
 
+    /// Optional number of times a looping animation plays, stored in the
+    /// `loop-count` key. It is ignored when the loop flag is off, and
+    /// implementations that do not know the key loop forever.
+    pub loop_count: Option<usize>,
+    /// Comments associated with the loop count.
+    pub loop_count_comments: Comments,
+
     /// Optional preview frame index.
     pub preview: Option<usize>,
     /// Comments associated with the preview.
@@ -145,6 +153,7 @@ impl Header {
         self.license_comments = Vec::new();
         self.delay_comments = Vec::new();
         self.loop_comments = Vec::new();
+        self.loop_count_comments = Vec::new();
         self.preview_comments = Vec::new();
         self.colors_comments = Vec::new();
         self.trailing_comments = Vec::new();
@@ -298,6 +307,10 @@ impl Header {
             write_comments(&self.loop_comments, f)?;
             writeln!(f, "loop {}", if *flag { "yes" } else { "no" })?;
         }
+        if let Some(count) = &self.loop_count {
+            write_comments(&self.loop_count_comments, f)?;
+            writeln!(f, "loop-count {}", count)?;
+        }
         if let Some(preview) = &self.preview {
             write_comments(&self.preview_comments, f)?;
             writeln!(f, "preview {}", preview)?;
@@ -328,6 +341,98 @@ impl Header {
     }
 }
 
+impl Header {
+    /// Formats the header in legacy 3a format with the given size and color mode.
+    /// Only the global delay is written. Keys without legacy counterparts,
+    /// such as the loop count, are omitted; extra keys are kept, as legacy
+    /// readers keep them as unknown keys. Comments are written on
+    /// tab-prefixed lines before their key, except that in headers read
+    /// from legacy files the last comment of a key is written after a tab
+    /// on its line, where legacy files usually have it.
+    pub(crate) fn fmt_legacy(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        info: LegacyHeaderInfo,
+        utf8: bool,
+    ) -> std::fmt::Result {
+        let write_key = |f: &mut std::fmt::Formatter<'_>, text: &str, comments: &Comments| {
+            write_legacy_key(f, text, comments, self.legacy.is_some())
+        };
+        writeln!(f, "width {}", info.width)?;
+        writeln!(f, "height {}", info.height)?;
+        if let Some(title) = &self.title {
+            write_key(f, &format!("title {}", title), &self.title_comments)?;
+        }
+        for (author, author_comments) in &self.authors {
+            write_key(f, &format!("author {}", author), author_comments)?;
+        }
+        if let Some(delay) = &self.delay {
+            let line = format!("delay {}", delay.get_global());
+            write_key(f, &line, &self.delay_comments)?;
+        }
+        if let Some(flag) = &self.loop_flag {
+            let line = format!("loop {}", if *flag { "true" } else { "false" });
+            write_key(f, &line, &self.loop_comments)?;
+        }
+        if let Some(preview) = &self.preview {
+            write_key(f, &format!("preview {}", preview), &self.preview_comments)?;
+        }
+        let mode = match info.colors {
+            LegacyColorMode::None => "none",
+            LegacyColorMode::FgOnly => "fg",
+            LegacyColorMode::BgOnly => "bg",
+            LegacyColorMode::FgAndBg => "full",
+        };
+        writeln!(f, "colors {}", mode)?;
+        if utf8 {
+            writeln!(f, "utf8")?;
+        }
+        for tagline in &self.tags {
+            let tags: Vec<String> = tagline.tags.iter().map(|tag| format!("#{}", tag)).collect();
+            write_key(f, &tags.join(" "), &tagline.comments)?;
+        }
+        for key in &self.extra_keys {
+            // Legacy keys are written from the art itself.
+            let name = key.line.split(' ').next().unwrap_or("");
+            if ["width", "height", "colors", "utf8"].contains(&name) {
+                continue;
+            }
+            write_key(f, &key.line, &key.comments)?;
+        }
+        write_legacy_comments(f, &self.trailing_comments)?;
+        writeln!(f)
+    }
+}
+
+/// Writes each comment to the formatter on its own line, prefixed by a tab
+/// as in legacy 3a format.
+fn write_legacy_comments(f: &mut std::fmt::Formatter<'_>, comments: &[String]) -> std::fmt::Result {
+    for c in comments {
+        writeln!(f, "\t{}", c)?;
+    }
+    Ok(())
+}
+
+/// Writes a legacy header line with its comments on their own lines before
+/// it, or with the last one after a tab on the line itself if `inline`.
+fn write_legacy_key(
+    f: &mut std::fmt::Formatter<'_>,
+    line: &str,
+    comments: &Comments,
+    inline: bool,
+) -> std::fmt::Result {
+    match comments.split_last() {
+        Some((last, before)) if inline && !last.is_empty() => {
+            write_legacy_comments(f, before)?;
+            writeln!(f, "{}\t{}", line, last)
+        }
+        _ => {
+            write_legacy_comments(f, comments)?;
+            writeln!(f, "{}", line)
+        }
+    }
+}
+
 impl fmt::Display for Header {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "@3a")?;
@@ -379,6 +484,12 @@ impl fmt::Display for Header {
             }
             writeln!(f, "loop {}", if *flag { "yes" } else { "no" })?;
         }
+        if let Some(count) = &self.loop_count {
+            for c in &self.loop_count_comments {
+                writeln!(f, ";; {}", c)?;
+            }
+            writeln!(f, "loop-count {}", count)?;
+        }
         if let Some(preview) = &self.preview {
             for c in &self.preview_comments {
                 writeln!(f, ";; {}", c)?;
@@ -408,23 +519,38 @@ impl fmt::Display for Header {
 }
 
 impl Header {
-    /// Reads a header from a buffered reader, automatically detecting modern
+    /// Reads a header from input lines, automatically detecting modern
     /// or legacy format.
     pub fn read<R: Read>(lines: &mut io::Lines<BufReader<R>>) -> Result<Self> {
+        Self::read_lines(&mut Lines::new(lines))
+    }
+
+    /// Reads a header like [`Header::read`] from [`Lines`], which counts
+    /// lines for error spans and carries the parse options.
+    pub fn read_lines<I: Iterator<Item = io::Result<String>>>(
+        lines: &mut Lines<I>,
+    ) -> Result<Self> {
         let fl = lines.next();
         if let Some(Ok(s)) = fl {
             if s == "@3a" {
-                Self::read_modern(lines)
+                let fixes = lines.fixes().len();
+                let result = Self::read_modern(lines);
+                let span = Span {
+                    block: Some("3a".into()),
+                    ..Span::default()
+                };
+                lines.locate_fixes(fixes, &span);
+                result.map_err(|err| lines.locate_block(err, "3a", None))
             } else {
-                Self::read_legacy(s.as_str(), lines)
+                Self::read_legacy(s.as_str(), lines).map_err(|err| lines.locate(err, None))
             }
         } else {
-            Self::read_legacy("@", lines)
+            Self::read_legacy("@", lines).map_err(|err| lines.locate(err, None))
         }
     }
-    pub(crate) fn read_legacy<R: Read>(
+    pub(crate) fn read_legacy<I: Iterator<Item = io::Result<String>>>(
         first: &str,
-        lines: &mut io::Lines<BufReader<R>>,
+        lines: &mut Lines<I>,
     ) -> Result<Self> {
         let mut header = Self::default();
         let mut comments_buffer = Vec::<String>::new();
@@ -440,8 +566,11 @@ impl Header {
             // }
             let line = match line.split_once("\t") {
                 Some((a, b)) => {
+                    let comment = normalize_text(b).trim().to_string();
+                    if a.is_empty() || !comment.is_empty() {
+                        comments_buffer.push(comment);
+                    }
                     if a.is_empty() {
-                        comments_buffer.push(normalize_text(b).trim().into());
                         continue;
                     }
                     a
@@ -561,10 +690,12 @@ impl Header {
         header.trailing_comments = comments_buffer;
         Ok(header)
     }
-    pub(crate) fn read_modern<R: Read>(lines: &mut io::Lines<BufReader<R>>) -> Result<Self> {
+    pub(crate) fn read_modern<I: Iterator<Item = io::Result<String>>>(
+        lines: &mut Lines<I>,
+    ) -> Result<Self> {
         let mut header = Self::default();
         let mut comments_buffer = Vec::<String>::new();
-        for line in lines {
+        while let Some(line) = lines.next() {
             let line = normalize_text(line?.as_str());
             if line.is_empty() {
                 break;
@@ -577,7 +708,9 @@ impl Header {
                 continue;
             }
             if line.starts_with("#") {
-                let mut tagline = line.parse::<Tagline>()?;
+                let mut tagline = line
+                    .parse::<Tagline>()
+                    .map_err(|err| lines.locate(err, Some(1)))?;
                 let tl = header.tags.len();
                 if tl > 0 && comments_buffer.len() == 0 {
                     for tag in tagline.tags {
@@ -591,129 +724,199 @@ impl Header {
                 continue;
             }
             let err = Error::HeaderKeyWithoutValue(line.clone());
-            let (key, values) = line.split_once(" ").ok_or(err)?;
+            let (key, values) = line
+                .split_once(" ")
+                .ok_or_else(|| lines.locate(err, Some(1)))?;
+            let value_column = line[..line.len() - values.trim_start().len()]
+                .chars()
+                .count()
+                + 1;
             let key = key.trim();
             let values = values.trim();
-            match key {
-                "title" => {
-                    if let Some(_) = header.title {
-                        return Err(Error::HeaderKeyDup(key.into()));
-                    }
-                    header.title = Some(values.into());
-                    header.title_comments = comments_buffer.clone();
+            let result = match header.read_modern_key(key, values, &comments_buffer) {
+                Err(err @ Error::HeaderKeyDup(_)) | Err(err @ Error::ColorMapDup(_))
+                    if lines.options().lenient =>
+                {
+                    header.clear_modern_key(key, values);
+                    let message = format!("duplicate header key '{}', last value is used", key);
+                    lines.fix(Some(1), err, message);
+                    header.read_modern_key(key, values, &comments_buffer)
                 }
-                "orig-author" => match header.orig_authors.get(values) {
-                    Some(comments) => {
-                        header.orig_authors.insert(
-                            values.into(),
-                            comments
-                                .into_iter()
-                                .map(|s| s.clone())
-                                .chain(comments_buffer.clone())
-                                .collect::<Vec<String>>(),
-                        );
-                    }
-                    None => {
-                        header
-                            .orig_authors
-                            .insert(values.into(), comments_buffer.clone());
-                    }
-                },
-                "author" => match header.authors.get(values) {
-                    Some(comments) => {
-                        header.authors.insert(
-                            values.into(),
-                            comments
-                                .into_iter()
-                                .map(|s| s.clone())
-                                .chain(comments_buffer.clone())
-                                .collect::<Vec<String>>(),
-                        );
-                    }
-                    None => {
-                        header
-                            .authors
-                            .insert(values.into(), comments_buffer.clone());
-                    }
-                },
-                "src" => {
-                    if let Some(_) = header.src {
-                        return Err(Error::HeaderKeyDup(key.into()));
+                result => result,
+            };
+            result.map_err(|err| {
+                let column = match &err {
+                    Error::HeaderKeyDup(_) => 1,
+                    Error::ColorParsing(value) | Error::ColorDuplicate(_, value)
+                        if key == "col" =>
+                    {
+                        color_pair_column(&line, value).unwrap_or(value_column)
                     }
-                    header.src = Some(values.into());
-                    header.src_comments = comments_buffer.clone();
+                    _ => value_column,
+                };
+                lines.locate(err, Some(column))
+            })?;
+            comments_buffer.clear();
+        }
+        header.trailing_comments = comments_buffer;
+        Ok(header)
+    }
+
+    /// Forgets the value set by a modern header key, so it can be set again.
+    fn clear_modern_key(&mut self, key: &str, values: &str) {
+        match key {
+            "title" => self.title = None,
+            "src" => self.src = None,
+            "editor" => self.editor = None,
+            "license" => self.license = None,
+            "delay" => self.delay = None,
+            "loop" => self.loop_flag = None,
+            "loop-count" => self.loop_count = None,
+            "preview" => self.preview = None,
+            "colors" => self.colors = None,
+            "col" => {
+                if let Ok(name) = color_name_str_to_char(values.split(" ").next()) {
+                    self.palette.remove_color(name);
                 }
-                "editor" => {
-                    if let Some(_) = header.editor {
-                        return Err(Error::HeaderKeyDup(key.into()));
-                    }
-                    header.editor = Some(values.into());
-                    header.editor_comments = comments_buffer.clone();
+            }
+            _ => {}
+        }
+    }
+
+    /// Applies a single `key values` line of a modern header.
+    pub(crate) fn read_modern_key(
+        &mut self,
+        key: &str,
+        values: &str,
+        comments_buffer: &[String],
+    ) -> Result<()> {
+        match key {
+            "title" => {
+                if let Some(_) = self.title {
+                    return Err(Error::HeaderKeyDup(key.into()));
                 }
-                "license" => {
-                    if let Some(_) = header.license {
-                        return Err(Error::HeaderKeyDup(key.into()));
-                    }
-                    header.license = Some(values.into());
-                    header.license_comments = comments_buffer.clone();
+                self.title = Some(values.into());
+                self.title_comments = comments_buffer.to_vec();
+            }
+            "orig-author" => match self.orig_authors.get(values) {
+                Some(comments) => {
+                    self.orig_authors.insert(
+                        values.into(),
+                        comments
+                            .into_iter()
+                            .map(|s| s.clone())
+                            .chain(comments_buffer.to_vec())
+                            .collect::<Vec<String>>(),
+                    );
                 }
-                "delay" => {
-                    if let Some(_) = header.delay {
-                        return Err(Error::HeaderKeyDup(key.into()));
-                    }
-                    header.delay = Some(values.parse()?);
-                    header.delay_comments = comments_buffer.clone();
+                None => {
+                    self.orig_authors
+                        .insert(values.into(), comments_buffer.to_vec());
                 }
-                "loop" => {
-                    if let Some(_) = header.loop_flag {
-                        return Err(Error::HeaderKeyDup(key.into()));
-                    }
-                    header.loop_flag = Some(header_value_to_bool(key, values)?);
-                    header.loop_comments = comments_buffer.clone();
+            },
+            "author" => match self.authors.get(values) {
+                Some(comments) => {
+                    self.authors.insert(
+                        values.into(),
+                        comments
+                            .into_iter()
+                            .map(|s| s.clone())
+                            .chain(comments_buffer.to_vec())
+                            .collect::<Vec<String>>(),
+                    );
                 }
-                "preview" => {
-                    if let Some(_) = header.preview {
-                        return Err(Error::HeaderKeyDup(key.into()));
+                None => {
+                    self.authors.insert(values.into(), comments_buffer.to_vec());
+                }
+            },
+            "src" => {
+                if let Some(_) = self.src {
+                    return Err(Error::HeaderKeyDup(key.into()));
+                }
+                self.src = Some(values.into());
+                self.src_comments = comments_buffer.to_vec();
+            }
+            "editor" => {
+                if let Some(_) = self.editor {
+                    return Err(Error::HeaderKeyDup(key.into()));
+                }
+                self.editor = Some(values.into());
+                self.editor_comments = comments_buffer.to_vec();
+            }
+            "license" => {
+                if let Some(_) = self.license {
+                    return Err(Error::HeaderKeyDup(key.into()));
+                }
+                self.license = Some(values.into());
+                self.license_comments = comments_buffer.to_vec();
+            }
+            "delay" => {
+                if let Some(_) = self.delay {
+                    return Err(Error::HeaderKeyDup(key.into()));
+                }
+                self.delay = Some(values.parse()?);
+                self.delay_comments = comments_buffer.to_vec();
+            }
+            "loop" => {
+                if let Some(_) = self.loop_flag {
+                    return Err(Error::HeaderKeyDup(key.into()));
+                }
+                self.loop_flag = Some(header_value_to_bool(key, values)?);
+                self.loop_comments = comments_buffer.to_vec();
+            }
+            "loop-count" => {
+                if self.loop_count.is_some() {
+                    return Err(Error::HeaderKeyDup(key.into()));
+                }
+                match values.parse::<usize>() {
+                    Ok(count) => {
+                        self.loop_count = Some(count);
+                        self.loop_count_comments = comments_buffer.to_vec();
                     }
-                    match values.parse::<usize>() {
-                        Ok(preview) => {
-                            header.preview = Some(preview);
-                            header.preview_comments = comments_buffer.clone();
-                        }
-                        Err(err) => {
-                            return Err(Error::PreviewParsing(values.into(), err));
-                        }
+                    Err(err) => {
+                        return Err(Error::LoopCountParsing(values.into(), err));
                     }
                 }
-                "colors" => {
-                    if let Some(_) = header.colors {
-                        return Err(Error::HeaderKeyDup(key.into()));
-                    }
-                    header.colors = Some(header_value_to_bool(key, values)?);
-                    header.colors_comments = comments_buffer.clone();
+            }
+            "preview" => {
+                if let Some(_) = self.preview {
+                    return Err(Error::HeaderKeyDup(key.into()));
                 }
-                "col" => {
-                    let mut values = values.split(" ");
-                    let n = values.next();
-                    let name = color_name_str_to_char(n)?;
-                    let strpair = values.collect::<Vec<&str>>().join(" ");
-                    let pair = strpair.parse::<ColorPair>()?;
-
-                    header
-                        .palette
-                        .add_parsing_color(name, pair, comments_buffer.clone())?;
+                match values.parse::<usize>() {
+                    Ok(preview) => {
+                        self.preview = Some(preview);
+                        self.preview_comments = comments_buffer.to_vec();
+                    }
+                    Err(err) => {
+                        return Err(Error::PreviewParsing(values.into(), err));
+                    }
                 }
-                _ => {
-                    header.extra_keys.push(ExtraHeaderKey {
-                        line: String::from(key) + " " + values,
-                        comments: comments_buffer.clone(),
-                    });
+            }
+            "colors" => {
+                if let Some(_) = self.colors {
+                    return Err(Error::HeaderKeyDup(key.into()));
                 }
-            };
-            comments_buffer.clear();
+                self.colors = Some(header_value_to_bool(key, values)?);
+                self.colors_comments = comments_buffer.to_vec();
+            }
+            "col" => {
+                let mut values = values.split(" ");
+                let n = values.next();
+                let name = color_name_str_to_char(n)?;
+                let strpair = values.collect::<Vec<&str>>().join(" ");
+                let pair = strpair.parse::<ColorPair>()?;
+
+                self.palette
+                    .add_parsing_color(name, pair, comments_buffer.to_vec())?;
+            }
+            _ => {
+                self.extra_keys.push(ExtraHeaderKey {
+                    line: String::from(key) + " " + values,
+                    comments: comments_buffer.to_vec(),
+                });
+            }
         }
-        header.trailing_comments = comments_buffer;
-        Ok(header)
+        Ok(())
     }
 }
 
@@ -727,6 +930,18 @@ fn header_value_to_bool(k: &str, v: &str) -> Result<bool> {
     }
 }
 
+/// Returns the 1-based column of `value` in the color pair of a `col` line,
+/// or of the start of the pair if it does not contain `value`.
+fn color_pair_column(line: &str, value: &str) -> Option<usize> {
+    let line = line.trim_end();
+    let values = line.split_once(' ')?.1.trim_start();
+    let pair = values.split_once(' ')?.1.trim_start();
+    let start = line[..line.len() - pair.len()].chars().count();
+    let pair = pair.to_lowercase();
+    let offset = pair.find(value).unwrap_or(0);
+    Some(start + pair[..offset].chars().count() + 1)
+}
+
 fn color_name_str_to_char(name: Option<&str>) -> Result<Char> {
     let name = name.unwrap_or_default();
     Char::from_str(name)
@@ -734,6 +949,7 @@ fn color_name_str_to_char(name: Option<&str>) -> Result<Char> {
 
 /// Represents an unrecognized header key‑value pair with its associated comments.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ExtraHeaderKey {
     /// The raw line content of the key and value.
     pub line: String,
@@ -742,6 +958,7 @@ pub struct ExtraHeaderKey {
 }
 /// A line containing one or more tags and optional comments.
 #[derive(Default, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Tagline {
     /// Set of tags on this line.
     pub tags: OrderSet<String>,
@@ -784,6 +1001,7 @@ impl FromStr for Tagline {
 
 /// Legacy color mode
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum LegacyColorMode {
     /// No colors used.
     None,
@@ -803,6 +1021,7 @@ impl Default for LegacyColorMode {
 
 /// Legacy header information for backward compatibility.
 #[derive(Default, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LegacyHeaderInfo {
     pub colors: LegacyColorMode,
     pub width: usize,
@@ -1,4 +1,4 @@
-use std::fmt::Write;
+use std::fmt::{self, Write};
 
 pub(crate) fn in_range(mut v: usize, start: usize, end: usize) -> usize {
     if v < start {
@@ -30,65 +30,6 @@ pub(crate) fn escape_html(s: &str) -> String {
     out
 }
 
-/// Given per-frame delays in milliseconds (len = N), produce:
-/// - total duration in seconds (f64)
-/// - `keyTimes` string (N+1 numbers separated by ';')
-/// - `values` strings for each frame (Vec<String>, each has N+1 semicolon-separated items)
-pub(crate) fn timing_for_svg(delays_ms: &[usize]) -> (f64, String, Vec<String>) {
-    let total_ms: usize = delays_ms.iter().sum();
-    let total_s = (total_ms as f64) / 1000.0;
-
-    // cumulative times in ms: length = N+1
-    let mut cum: Vec<usize> = Vec::with_capacity(delays_ms.len() + 1);
-    let mut acc = 0usize;
-    cum.push(acc);
-    for &d in delays_ms {
-        acc += d;
-        cum.push(acc);
-    }
-
-    // keyTimes fractions as strings with fixed precision
-    // We'll print 6 decimal places (fine for SVG); trim trailing zeros optional
-    let key_times_parts: Vec<String> = cum
-        .iter()
-        .map(|&ms| {
-            let frac = (ms as f64) / (total_ms as f64);
-            // format with 6 decimals, remove trailing zeros for compactness
-            let mut s = format!("{:.6}", frac);
-            // trim trailing zeros and trailing dot
-            while s.contains('.') && (s.ends_with('0') || s.ends_with('.')) {
-                if s.ends_with('0') {
-                    s.pop();
-                } else if s.ends_with('.') {
-                    s.pop();
-                    break;
-                }
-            }
-            if s.is_empty() {
-                s = "0".into();
-            }
-            s
-        })
-        .collect();
-
-    let key_times = key_times_parts.join(";");
-
-    // build values per frame: for N frames we need N+1 items in each values
-    let n = delays_ms.len();
-    let mut values_vec: Vec<String> = Vec::with_capacity(n);
-    for k in 0..n {
-        // values[i] == "1" if i == k else "0"
-        let mut parts = Vec::with_capacity(n + 1);
-        for i in 0..=n {
-            parts.push(if i == k { "1" } else { "0" }.to_string());
-        }
-        let joined = parts.join(";");
-        values_vec.push(joined);
-    }
-
-    (total_s, key_times, values_vec)
-}
-
 /// Return a quoted JSON string (including the surrounding `"`).
 /// - `"` and `\` are escaped.
 /// - C0 controls (U+0000..U+001F) and C1 controls (U+007F..U+009F)
@@ -117,9 +58,46 @@ pub(crate) fn json_quote(s: &str) -> String {
     out
 }
 
+/// Replaces `{{key}}` placeholders in a single pass,
+/// so substituted values are never scanned for placeholders themselves.
+/// Unknown placeholders are kept as is.
+pub(crate) fn fill_template(template: &str, values: &[(&str, &str)]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let tail = &rest[start + 2..];
+        let value = tail
+            .find("}}")
+            .and_then(|end| values.iter().find(|(k, _)| *k == &tail[..end]));
+        match value {
+            Some((key, value)) => {
+                out.push_str(value);
+                rest = &tail[key.len() + 2..];
+            }
+            None => {
+                out.push_str("{{");
+                rest = tail;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Adapts a formatting function to `Display`, so it can be written to `io::Write`
+/// or collected to a string.
+pub(crate) struct Fmt<F: Fn(&mut fmt::Formatter<'_>) -> fmt::Result>(pub F);
+
+impl<F: Fn(&mut fmt::Formatter<'_>) -> fmt::Result> fmt::Display for Fmt<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (self.0)(f)
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::escape_html;
+    use super::{escape_html, fill_template};
 
     #[test]
     fn basic() {
@@ -136,6 +114,12 @@ mod tests {
         assert_eq!(escape_html("&amp;"), "&amp;amp;");
     }
 
+    #[test]
+    fn template_single_pass() {
+        let out = fill_template("{{a}} {{b}} {{c}}", &[("a", "{{b}}"), ("b", "x")]);
+        assert_eq!(out, "{{b}} x {{c}}");
+    }
+
     #[test]
     fn script_injection_becomes_safe() {
         assert_eq!(escape_html("</script>"), "&lt;&#x2F;script&gt;");
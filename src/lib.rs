@@ -1,20 +1,44 @@
 #![doc = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/README.md"))]
 pub mod art;
+mod asciicast;
+pub mod bitmap;
 pub mod chars;
 pub mod colors;
 pub mod comments;
 pub mod content;
 pub mod delay;
+pub mod document;
 pub mod error;
 pub mod font;
+mod gif;
 pub mod header;
 mod helpers;
+mod json;
+pub mod lines;
+pub mod lint;
+mod png;
+mod raster;
+pub mod reader;
+mod svg;
+pub mod term;
+pub mod timeline;
 mod ttyrec;
+pub mod writer;
 
 pub use art::Art;
+pub use asciicast::AsciicastOptions;
+pub use bitmap::{BitmapFont, Glyph};
 pub use colors::{CSSColorMap, Color, Color4, ColorPair, Palette};
 pub use comments::Comments;
 pub use content::{Cell, Frame, Frames};
 pub use delay::Delay;
-pub use error::{Error, Result};
+pub use document::Document;
+pub use error::{Error, Result, Span};
 pub use header::{ExtraHeaderKey, Header, LegacyColorMode, LegacyHeaderInfo, Tagline};
+pub use lines::{Fix, ParseOptions};
+pub use reader::ArtReader;
+pub use svg::{SvgAnimation, SvgOptions};
+pub use term::Terminal;
+pub use timeline::Timeline;
+pub use ttyrec::TtyrecOptions;
+pub use writer::ArtWriter;
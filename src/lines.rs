@@ -0,0 +1,145 @@
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read};
+
+use crate::content::Cell;
+use crate::error::{Error, Span};
+
+/// Options for reading 3a art.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Repairs problems instead of failing: ragged rows are padded or
+    /// truncated, missing color columns are filled, frames are fitted to the
+    /// size of the first one and duplicate header keys are overridden by the
+    /// last occurrence. Every repair is reported as a [`Fix`], as are comments
+    /// within legacy bodies, which are moved to the header in either mode.
+    pub lenient: bool,
+    /// Cell used to pad rows and frames in lenient mode.
+    pub pad_with: Cell,
+}
+
+/// A repair applied while parsing in lenient mode.
+#[derive(Debug, Clone)]
+pub struct Fix {
+    pub span: Span,
+    pub message: String,
+    /// The error a strict parse would have failed with, if it fails.
+    pub error: Option<Error>,
+}
+
+impl fmt::Display for Fix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.span, self.message)
+    }
+}
+
+/// Iterator over input lines that counts them, so parse errors can point
+/// to the line they were found on. It also carries the parse options and
+/// collects the fixes applied with them.
+#[derive(Debug)]
+pub struct Lines<I> {
+    inner: I,
+    line: usize,
+    options: ParseOptions,
+    fixes: Vec<Fix>,
+}
+
+impl<R: Read> Lines<io::Lines<BufReader<R>>> {
+    /// Reads lines from any reader.
+    pub fn from_reader(r: R) -> Self {
+        Self::new(BufReader::new(r).lines())
+    }
+}
+
+impl<I: Iterator<Item = io::Result<String>>> Lines<I> {
+    /// Wraps an iterator of lines.
+    pub fn new(inner: I) -> Self {
+        Self::with_options(inner, ParseOptions::default())
+    }
+
+    /// Wraps an iterator of lines, to be read with the given options.
+    pub fn with_options(inner: I, options: ParseOptions) -> Self {
+        Self {
+            inner,
+            line: 0,
+            options,
+            fixes: Vec::new(),
+        }
+    }
+
+    /// Returns the parse options.
+    pub fn options(&self) -> &ParseOptions {
+        &self.options
+    }
+
+    /// Returns the fixes applied so far.
+    pub fn fixes(&self) -> &[Fix] {
+        &self.fixes
+    }
+
+    /// Consumes the reader, returning the fixes applied.
+    pub fn into_fixes(self) -> Vec<Fix> {
+        self.fixes
+    }
+
+    /// Returns the 1-based number of the last line read, 0 before the first.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Attaches the current line and an optional 1-based column to an error.
+    pub(crate) fn locate(&self, err: Error, column: Option<usize>) -> Error {
+        err.locate(Span {
+            line: self.line,
+            column,
+            ..Span::default()
+        })
+    }
+
+    /// Records a fix of `error` on the current line.
+    pub(crate) fn fix(&mut self, column: Option<usize>, error: Error, message: String) {
+        let span = Span {
+            line: self.line,
+            column,
+            ..Span::default()
+        };
+        self.fix_at(span, Some(error), message);
+    }
+
+    /// Records a fix of `error`, if any, at the given location.
+    pub(crate) fn fix_at(&mut self, span: Span, error: Option<Error>, message: String) {
+        self.fixes.push(Fix {
+            span,
+            message,
+            error,
+        });
+    }
+
+    /// Completes the location of fixes recorded since the `from`-th one.
+    pub(crate) fn locate_fixes(&mut self, from: usize, span: &Span) {
+        for fix in &mut self.fixes[from..] {
+            fix.span.fill(span);
+        }
+    }
+
+    /// Attaches the current line, block title and optional frame index to an error.
+    pub(crate) fn locate_block(&self, err: Error, block: &str, frame: Option<usize>) -> Error {
+        err.locate(Span {
+            line: self.line,
+            column: None,
+            block: Some(block.into()),
+            frame,
+        })
+    }
+}
+
+impl<I: Iterator<Item = io::Result<String>>> Iterator for Lines<I> {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let line = self.inner.next();
+        if line.is_some() {
+            self.line += 1;
+        }
+        line
+    }
+}
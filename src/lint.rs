@@ -0,0 +1,430 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+
+use crate::art::Art;
+use crate::chars::normalize_text;
+use crate::error::{Error, Result, Span};
+use crate::lines::{Lines, ParseOptions};
+
+/// Keys that only have a meaning in the legacy format.
+const LEGACY_KEYS: [&str; 3] = ["width", "height", "utf8"];
+
+/// Color names that resolve without a `col` mapping.
+const BUILTIN_COLORS: &str = "_0123456789abcdef";
+
+/// How serious a finding is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// The file is valid but likely not what was intended.
+    Warning,
+    /// The file cannot be read by a strict parser.
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A single problem found in a 3a source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Stable kebab-case identifier of the check, e.g. `unused-color`.
+    pub code: &'static str,
+    pub message: String,
+    pub span: Span,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {}[{}]: {}",
+            self.span, self.severity, self.code, self.message
+        )
+    }
+}
+
+/// Returns true if any diagnostic is an error.
+pub fn has_errors(diagnostics: &[Diagnostic]) -> bool {
+    diagnostics.iter().any(|d| d.severity == Severity::Error)
+}
+
+/// Lints a 3a file.
+pub fn lint_file<P: AsRef<Path>>(path: P) -> Result<Vec<Diagnostic>> {
+    lint_reader(fs::File::open(path)?)
+}
+
+/// Lints a 3a document from any reader.
+pub fn lint_reader<R: Read>(mut r: R) -> Result<Vec<Diagnostic>> {
+    let mut src = String::new();
+    r.read_to_string(&mut src).map_err(|err| match err.kind() {
+        io::ErrorKind::InvalidData => Error::NotUtf8,
+        _ => err.into(),
+    })?;
+    Ok(lint_str(&src))
+}
+
+/// Lints a 3a document, collecting every problem found instead of stopping
+/// at the first one. Diagnostics are ordered by location.
+///
+/// The source is read by the parser in lenient mode: every repair it makes
+/// is reported as an error, as is the error it stops at, if any, and comments
+/// it moves out of legacy bodies as warnings. Files that
+/// parse are then checked for unused and unmapped colors and for delays and
+/// previews pointing past the last frame.
+///
+/// Legacy files only get the parser checks and a `legacy-format` warning;
+/// the other checks need the key lines of a modern header.
+pub fn lint_str(src: &str) -> Vec<Diagnostic> {
+    let mut linter = Linter {
+        diagnostics: Vec::new(),
+    };
+    let source: Vec<&str> = src.lines().collect();
+    let modern = source.first() == Some(&"@3a");
+    if !modern {
+        linter.warning(
+            "legacy-format",
+            "file uses the legacy 3a format".into(),
+            span(1, None, None, None),
+        );
+    }
+    let options = ParseOptions {
+        lenient: true,
+        ..ParseOptions::default()
+    };
+    let mut lines = Lines::with_options(BufReader::new(src.as_bytes()).lines(), options);
+    let mut blocks = Vec::new();
+    let parsed = Art::read_lines_with_blocks(&mut lines, &mut blocks);
+    for fix in lines.fixes() {
+        match &fix.error {
+            Some(err) => linter.error(error_code(err), fix.message.clone(), fix.span.clone()),
+            None => linter.warning("moved-comment", fix.message.clone(), fix.span.clone()),
+        }
+    }
+    let art = match parsed {
+        Ok(art) => Some(art),
+        Err(err) => {
+            let span = err.span().cloned().unwrap_or_default();
+            linter.error(error_code(&err), err.inner().to_string(), span);
+            None
+        }
+    };
+    if modern {
+        let index = linter.header(&source);
+        for (title, line) in &blocks {
+            linter.trailing_whitespace(source[line - 1], *line, title);
+        }
+        if let Some(art) = art {
+            let body = blocks
+                .iter()
+                .find(|(title, _)| title == "body")
+                .map_or(0, |(_, line)| *line);
+            linter.check_palette(&art, &index, body);
+            linter.check_delays(&art, &index);
+        }
+    }
+    let mut diagnostics = linter.diagnostics;
+    diagnostics.sort_by_key(|d| (d.span.line, d.span.column));
+    diagnostics
+}
+
+/// Returns the diagnostic code for a parse error.
+fn error_code(err: &Error) -> &'static str {
+    match err.inner() {
+        Error::HeaderKeyDup(_) => "duplicate-key",
+        Error::HeaderKeyWithoutValue(_) => "missing-value",
+        Error::ColorDuplicate(..) | Error::ColorMapDup(_) => "duplicate-color",
+        Error::DelayLineParsing(_)
+        | Error::DelayLineVoid(_)
+        | Error::GlobalDelayParsing(..)
+        | Error::GlobalDelayDup(_)
+        | Error::PerFrameDelayParsing(..)
+        | Error::PerFrameDelayDup(..)
+        | Error::ColorParsing(_)
+        | Error::ColorName(_)
+        | Error::HeaderFlagKey(_)
+        | Error::PreviewParsing(..)
+        | Error::LoopCountParsing(..) => "invalid-value",
+        Error::WidthMismatch => "width-mismatch",
+        Error::HeightMismatch => "height-mismatch",
+        Error::BlockDup(_) => "duplicate-block",
+        Error::BlockExpected(_) => "block-expected",
+        _ => "parse-error",
+    }
+}
+
+fn span(line: usize, column: Option<usize>, block: Option<&str>, frame: Option<usize>) -> Span {
+    Span {
+        line,
+        column,
+        block: block.map(String::from),
+        frame,
+    }
+}
+
+/// Where header keys were found, to point warnings at them.
+#[derive(Default)]
+struct HeaderIndex {
+    /// Line and value column of the first occurrence of every key.
+    keys: HashMap<String, (usize, usize)>,
+    /// Line and column of the first `col` entry of every color name.
+    colors: HashMap<String, (usize, usize)>,
+}
+
+impl HeaderIndex {
+    fn key_span(&self, key: &str) -> Span {
+        let (line, column) = self.keys.get(key).copied().unwrap_or_default();
+        span(line, Some(column), Some("3a"), None)
+    }
+
+    fn color_span(&self, name: &str) -> Span {
+        let (line, column) = self.colors.get(name).copied().unwrap_or_default();
+        span(line, Some(column), Some("3a"), None)
+    }
+}
+
+struct Linter {
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Linter {
+    fn error(&mut self, code: &'static str, message: String, span: Span) {
+        self.diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            code,
+            message,
+            span,
+        });
+    }
+
+    fn warning(&mut self, code: &'static str, message: String, span: Span) {
+        self.diagnostics.push(Diagnostic {
+            severity: Severity::Warning,
+            code,
+            message,
+            span,
+        });
+    }
+
+    /// Warns about trailing whitespace on a line outside of frames,
+    /// where it is never significant.
+    fn trailing_whitespace(&mut self, line: &str, number: usize, block: &str) {
+        let trimmed = line.trim_end();
+        if trimmed.len() != line.len() && !trimmed.is_empty() {
+            let column = trimmed.chars().count() + 1;
+            let span = span(number, Some(column), Some(block), None);
+            self.warning("trailing-whitespace", "trailing whitespace".into(), span);
+        }
+    }
+
+    /// Checks the lines of a modern header for problems the parser accepts,
+    /// and records where every key was found.
+    fn header(&mut self, source: &[&str]) -> HeaderIndex {
+        let mut index = HeaderIndex::default();
+        let mut tags: HashMap<String, usize> = HashMap::new();
+        for (number, raw) in source.iter().enumerate().skip(1) {
+            let number = number + 1;
+            self.trailing_whitespace(raw, number, "3a");
+            let line = normalize_text(raw);
+            if line.is_empty() {
+                break;
+            }
+            if line.starts_with(";;") {
+                continue;
+            }
+            if line.starts_with('#') {
+                let mut column = 1;
+                for word in line.split(' ') {
+                    if let Some(tag) = word.strip_prefix('#') {
+                        if let Some(first) = tags.get(tag) {
+                            let message = format!("tag #{} already set on line {}", tag, first);
+                            let span = span(number, Some(column), Some("3a"), None);
+                            self.warning("duplicate-tag", message, span);
+                        } else {
+                            tags.insert(tag.into(), number);
+                        }
+                    }
+                    column += word.chars().count() + 1;
+                }
+                continue;
+            }
+            let (key, values) = match line.split_once(' ') {
+                Some(kv) => kv,
+                None => continue,
+            };
+            let value_column = line[..line.len() - values.trim_start().len()]
+                .chars()
+                .count()
+                + 1;
+            let key = key.trim();
+            if LEGACY_KEYS.contains(&key) {
+                let message = format!("'{}' is only meaningful in the legacy format", line);
+                self.warning(
+                    "legacy-key",
+                    message,
+                    span(number, Some(1), Some("3a"), None),
+                );
+            }
+            if key == "col" {
+                if let Some(name) = values.trim().split(' ').next() {
+                    let location = (number, value_column);
+                    index.colors.entry(name.into()).or_insert(location);
+                }
+            }
+            let location = (number, value_column);
+            index.keys.entry(key.into()).or_insert(location);
+        }
+        index
+    }
+
+    /// Checks that every mapped color is used and every used color is
+    /// mapped. Unmapped colors are pointed at the first frame using them,
+    /// in the body block starting on line `body`.
+    fn check_palette(&mut self, art: &Art, index: &HeaderIndex, body: usize) {
+        let frames = &art.frames.frames;
+        for name in art.header.palette.palette.keys() {
+            if !frames.iter().any(|frame| frame.contains_color(*name)) {
+                let message = format!("color '{}' is never used", name);
+                let span = index.color_span(&name.to_string());
+                self.warning("unused-color", message, span);
+            }
+        }
+        let mut reported = Vec::new();
+        for (i, frame) in frames.iter().enumerate() {
+            for cell in frame.rows.iter().flatten() {
+                let name = match cell.color {
+                    Some(name) => name,
+                    None => continue,
+                };
+                if art.header.palette.contains_color(name)
+                    || BUILTIN_COLORS.contains(name.char)
+                    || reported.contains(&name)
+                {
+                    continue;
+                }
+                reported.push(name);
+                let message = format!("color '{}' has no col mapping", name);
+                let span = span(body, None, Some("body"), Some(i));
+                self.warning("unmapped-color", message, span);
+            }
+        }
+    }
+
+    fn check_delays(&mut self, art: &Art, index: &HeaderIndex) {
+        let count = art.frames.frames.len();
+        if let Some(delay) = &art.header.delay {
+            let mut frames: Vec<usize> = delay.per_frame.keys().copied().collect();
+            frames.sort_unstable();
+            for frame in frames.into_iter().filter(|f| *f >= count) {
+                let message = format!(
+                    "delay set for frame {}, but there are only {} frames",
+                    frame, count
+                );
+                self.warning("delay-out-of-range", message, index.key_span("delay"));
+            }
+        }
+        if let Some(preview) = art.header.preview {
+            if preview >= count {
+                let message = format!(
+                    "preview frame {} does not exist, there are only {} frames",
+                    preview, count
+                );
+                self.warning("preview-out-of-range", message, index.key_span("preview"));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codes(src: &str) -> Vec<(&'static str, usize)> {
+        lint_str(src)
+            .iter()
+            .map(|d| (d.code, d.span.line))
+            .collect()
+    }
+
+    #[test]
+    fn clean_file() {
+        let src = "@3a\ntitle ok\ncolors yes\ncol x fg:red\n\n@body\nab1x\n\n";
+        assert_eq!(lint_str(src), vec![]);
+    }
+
+    #[test]
+    fn collects_every_problem() {
+        let src = concat!(
+            "@3a\n",
+            "title a \n",
+            "title b\n",
+            "#x #y #x\n",
+            "width 10\n",
+            "colors yes\n",
+            "col u fg:red\n",
+            "delay 50 7:100\n",
+            "preview 4\n",
+            "\n",
+            "@body\n",
+            "ab1z\n",
+            "abc1z\n",
+            "\n",
+            "ab12\n",
+            "\n",
+        );
+        assert_eq!(
+            codes(src),
+            vec![
+                ("trailing-whitespace", 2),
+                ("duplicate-key", 3),
+                ("duplicate-tag", 4),
+                ("legacy-key", 5),
+                ("unused-color", 7),
+                ("delay-out-of-range", 8),
+                ("preview-out-of-range", 9),
+                ("unmapped-color", 11),
+                ("width-mismatch", 13),
+                ("height-mismatch", 15),
+            ]
+        );
+        let diagnostics = lint_str(src);
+        assert!(has_errors(&diagnostics));
+        assert_eq!(
+            diagnostics[2].to_string(),
+            "line 4, column 7 (block 3a): warning[duplicate-tag]: tag #x already set on line 4"
+        );
+    }
+
+    #[test]
+    fn falls_back_to_parser_errors() {
+        assert_eq!(
+            codes("width x\nheight 1\n\nab\n"),
+            vec![("legacy-format", 1), ("invalid-value", 1)]
+        );
+        assert_eq!(
+            codes("width 1\nheight 1\n\na\tfirst\nb\n"),
+            vec![("legacy-format", 1), ("moved-comment", 4)]
+        );
+    }
+
+    #[test]
+    fn reader_errors() {
+        struct Broken;
+        impl Read for Broken {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::new(io::ErrorKind::Other, "broken"))
+            }
+        }
+        assert!(matches!(lint_reader(Broken), Err(Error::Io(_))));
+        assert!(matches!(lint_reader(&[0xff][..]), Err(Error::NotUtf8)));
+    }
+}
@@ -0,0 +1,297 @@
+use crate::raster::{shared_palette, Canvas};
+
+const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Encodes a single image as PNG.
+pub(crate) fn encode(canvas: &Canvas) -> Vec<u8> {
+    encode_frames(std::slice::from_ref(canvas), None)
+}
+
+/// Encodes frames as an animated PNG.
+/// `delays` are in milliseconds; `plays` is the number of times the
+/// animation is shown, 0 meaning forever.
+pub(crate) fn encode_animated(frames: &[Canvas], delays: &[usize], plays: u32) -> Vec<u8> {
+    encode_frames(frames, Some((delays, plays)))
+}
+
+fn encode_frames(frames: &[Canvas], animation: Option<(&[usize], u32)>) -> Vec<u8> {
+    let width = frames.first().map(|f| f.width).unwrap_or(0);
+    let height = frames.first().map(|f| f.height).unwrap_or(0);
+    let palette = shared_palette(frames);
+
+    let mut out = SIGNATURE.to_vec();
+    let mut ihdr = Vec::new();
+    ihdr.extend_from_slice(&(width as u32).to_be_bytes());
+    ihdr.extend_from_slice(&(height as u32).to_be_bytes());
+    // Bit depth 8; indexed or truecolor; default compression, filter, no interlace.
+    ihdr.extend_from_slice(&[8, if palette.is_some() { 3 } else { 2 }, 0, 0, 0]);
+    push_chunk(&mut out, b"IHDR", &ihdr);
+    if let Some((table, _)) = &palette {
+        let plte: Vec<u8> = table
+            .iter()
+            .flat_map(|(r, g, b)| vec![*r, *g, *b])
+            .collect();
+        push_chunk(&mut out, b"PLTE", &plte);
+    }
+    if let Some((_, plays)) = animation {
+        let mut actl = Vec::new();
+        actl.extend_from_slice(&(frames.len() as u32).to_be_bytes());
+        actl.extend_from_slice(&plays.to_be_bytes());
+        push_chunk(&mut out, b"acTL", &actl);
+    }
+
+    let mut sequence: u32 = 0;
+    for (f, frame) in frames.iter().enumerate() {
+        if let Some((delays, _)) = animation {
+            let delay = delays.get(f).copied().unwrap_or(0);
+            // Delays that do not fit into milliseconds are stored in centiseconds.
+            let (num, den) = if delay <= u16::MAX as usize {
+                (delay as u16, 1000u16)
+            } else {
+                ((delay / 10).min(u16::MAX as usize) as u16, 100u16)
+            };
+            let mut fctl = Vec::new();
+            fctl.extend_from_slice(&sequence.to_be_bytes());
+            fctl.extend_from_slice(&(frame.width as u32).to_be_bytes());
+            fctl.extend_from_slice(&(frame.height as u32).to_be_bytes());
+            fctl.extend_from_slice(&[0; 8]); // x and y offsets
+            fctl.extend_from_slice(&num.to_be_bytes());
+            fctl.extend_from_slice(&den.to_be_bytes());
+            fctl.extend_from_slice(&[0, 0]); // no disposal, source blending
+            push_chunk(&mut out, b"fcTL", &fctl);
+            sequence += 1;
+        }
+
+        let mut raw = Vec::with_capacity(frame.pixels.len() * 3 + frame.height);
+        for row in frame.pixels.chunks(frame.width.max(1)) {
+            raw.push(0); // no filter
+            for px in row {
+                match &palette {
+                    Some((_, index)) => raw.push(index[px]),
+                    None => raw.extend_from_slice(&[px.0, px.1, px.2]),
+                }
+            }
+        }
+        let data = zlib_compress(&raw);
+        if f == 0 {
+            push_chunk(&mut out, b"IDAT", &data);
+        } else {
+            let mut fdat = sequence.to_be_bytes().to_vec();
+            fdat.extend_from_slice(&data);
+            push_chunk(&mut out, b"fdAT", &fdat);
+            sequence += 1;
+        }
+    }
+    push_chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+fn push_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut table = [0u32; 256];
+    for (n, entry) in table.iter_mut().enumerate() {
+        let mut c = n as u32;
+        for _ in 0..8 {
+            c = if c & 1 != 0 {
+                0xEDB88320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+        }
+        *entry = c;
+    }
+    let mut crc = 0xFFFFFFFF;
+    for byte in data {
+        crc = table[((crc ^ *byte as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    crc ^ 0xFFFFFFFF
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for byte in chunk {
+            a += *byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+/// Packs bits into bytes, least significant bit first.
+struct BitWriter {
+    out: Vec<u8>,
+    acc: u32,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, count: u32) {
+        self.acc |= value << self.bits;
+        self.bits += count;
+        while self.bits >= 8 {
+            self.out.push(self.acc as u8);
+            self.acc >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    /// Writes a Huffman code, which is stored most significant bit first.
+    fn write_code(&mut self, code: u32, len: u32) {
+        let mut reversed = 0;
+        for i in 0..len {
+            reversed |= ((code >> i) & 1) << (len - 1 - i);
+        }
+        self.write(reversed, len);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.out.push(self.acc as u8);
+        }
+        self.out
+    }
+}
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+const WINDOW: usize = 32768;
+const MAX_MATCH: usize = 258;
+const MAX_CHAIN: usize = 64;
+
+fn write_literal(w: &mut BitWriter, value: u16) {
+    match value {
+        0..=143 => w.write_code(0x30 + value as u32, 8),
+        144..=255 => w.write_code(0x190 + (value - 144) as u32, 9),
+        256..=279 => w.write_code((value - 256) as u32, 7),
+        _ => w.write_code(0xC0 + (value - 280) as u32, 8),
+    }
+}
+
+fn write_match(w: &mut BitWriter, len: usize, dist: usize) {
+    let l = LENGTH_BASE
+        .iter()
+        .rposition(|b| *b as usize <= len)
+        .unwrap_or(0);
+    write_literal(w, 257 + l as u16);
+    w.write(
+        (len - LENGTH_BASE[l] as usize) as u32,
+        LENGTH_EXTRA[l] as u32,
+    );
+    let d = DIST_BASE
+        .iter()
+        .rposition(|b| *b as usize <= dist)
+        .unwrap_or(0);
+    w.write_code(d as u32, 5);
+    w.write((dist - DIST_BASE[d] as usize) as u32, DIST_EXTRA[d] as u32);
+}
+
+fn hash(data: &[u8], i: usize) -> usize {
+    ((data[i] as usize) << 10 ^ (data[i + 1] as usize) << 5 ^ data[i + 2] as usize) & 0x7FFF
+}
+
+/// Links position `i` into the hash chains.
+fn insert(data: &[u8], i: usize, head: &mut [usize], prev: &mut [usize]) {
+    if i + 2 < data.len() {
+        let h = hash(data, i);
+        prev[i] = head[h];
+        head[h] = i;
+    }
+}
+
+/// Compresses data into a zlib stream of a single fixed Huffman deflate block,
+/// using greedy LZ77 matching over hashed 3-byte prefixes.
+fn zlib_compress(data: &[u8]) -> Vec<u8> {
+    let mut w = BitWriter {
+        out: vec![0x78, 0x01],
+        acc: 0,
+        bits: 0,
+    };
+    w.write(1, 1); // final block
+    w.write(1, 2); // fixed Huffman codes
+    let mut head = vec![usize::MAX; 0x8000];
+    let mut prev = vec![usize::MAX; data.len()];
+    let mut i = 0;
+    while i < data.len() {
+        let mut best = (0, 0);
+        if i + 2 < data.len() {
+            let mut candidate = head[hash(data, i)];
+            let mut chain = 0;
+            while candidate != usize::MAX && i - candidate <= WINDOW && chain < MAX_CHAIN {
+                let max = MAX_MATCH.min(data.len() - i);
+                let mut len = 0;
+                while len < max && data[candidate + len] == data[i + len] {
+                    len += 1;
+                }
+                if len > best.0 {
+                    best = (len, i - candidate);
+                    if len == max {
+                        break;
+                    }
+                }
+                candidate = prev[candidate];
+                chain += 1;
+            }
+        }
+        if best.0 >= 3 {
+            write_match(&mut w, best.0, best.1);
+            for j in i..i + best.0 {
+                insert(data, j, &mut head, &mut prev);
+            }
+            i += best.0;
+        } else {
+            write_literal(&mut w, data[i] as u16);
+            insert(data, i, &mut head, &mut prev);
+            i += 1;
+        }
+    }
+    write_literal(&mut w, 256);
+    let mut out = w.finish();
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksums() {
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E60398);
+    }
+
+    #[test]
+    fn chunk_layout() {
+        let png = encode(&Canvas::new(3, 2, (1, 2, 3)));
+        assert_eq!(&png[..8], SIGNATURE);
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(&png[16..24], &[0, 0, 0, 3, 0, 0, 0, 2]);
+        assert_eq!(png[25], 3);
+        assert_eq!(&png[png.len() - 8..png.len() - 4], b"IEND");
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+
+use crate::bitmap::BitmapFont;
+use crate::colors::{CSSColorMap, Color, ColorPair, Palette};
+use crate::content::Frame;
+use crate::font::Font;
+
+/// RGB pixel.
+pub(crate) type Rgb = (u8, u8, u8);
+
+/// An RGB image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Canvas {
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+    pub(crate) pixels: Vec<Rgb>,
+}
+
+impl Canvas {
+    pub(crate) fn new(width: usize, height: usize, fill: Rgb) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![fill; width * height],
+        }
+    }
+
+    fn fill_rect(&mut self, x: usize, y: usize, w: usize, h: usize, color: Rgb) {
+        for py in y..(y + h).min(self.height) {
+            for px in x..(x + w).min(self.width) {
+                self.pixels[py * self.width + px] = color;
+            }
+        }
+    }
+
+    fn put(&mut self, x: usize, y: usize, color: Rgb) {
+        if x < self.width && y < self.height {
+            self.pixels[y * self.width + x] = color;
+        }
+    }
+}
+
+/// Builds a color table shared by all frames with a pixel to index map,
+/// if they use at most 256 distinct colors.
+pub(crate) fn shared_palette(frames: &[Canvas]) -> Option<(Vec<Rgb>, HashMap<Rgb, u8>)> {
+    let mut table: Vec<Rgb> = Vec::new();
+    let mut index: HashMap<Rgb, u8> = HashMap::new();
+    for frame in frames {
+        for px in &frame.pixels {
+            if !index.contains_key(px) {
+                if table.len() == 256 {
+                    return None;
+                }
+                index.insert(*px, table.len() as u8);
+                table.push(*px);
+            }
+        }
+    }
+    Some((table, index))
+}
+
+/// Rasterizes a frame: every cell is `font.width` x `font.height` pixels
+/// filled with its background, with the glyph drawn at the foreground offsets.
+/// Characters missing from `glyphs` are drawn as a hollow box.
+pub(crate) fn render_frame(
+    frame: &Frame,
+    colors: bool,
+    palette: &Palette,
+    map: &CSSColorMap,
+    font: &Font,
+    glyphs: &BitmapFont,
+) -> Canvas {
+    let mut canvas = Canvas::new(
+        frame.width() * font.width,
+        frame.height() * font.height,
+        map.map_rgb(Color::None, false),
+    );
+    for r in 0..frame.height() {
+        for c in 0..frame.width() {
+            let cell = frame.rows[r][c];
+            let pair = if colors {
+                cell.to_pair(palette)
+            } else {
+                ColorPair::default()
+            };
+            let x = c * font.width;
+            let y = r * font.height;
+            let bg = map.map_rgb(pair.bg, false);
+            let fg = map.map_rgb(pair.fg, true);
+            canvas.fill_rect(x, y, font.width, font.height, bg);
+            if cell.text.char == ' ' {
+                continue;
+            }
+            // Glyphs are clipped to their cell.
+            let gx = x + font.fg_offset_x;
+            let gy = y + font.fg_offset_y;
+            let gw = glyphs
+                .width()
+                .min(font.width.saturating_sub(font.fg_offset_x));
+            let gh = glyphs
+                .height()
+                .min(font.height.saturating_sub(font.fg_offset_y));
+            match glyphs.glyph(cell.text) {
+                Some(glyph) => {
+                    for py in 0..gh {
+                        for px in 0..gw {
+                            if glyph.get(px, py) {
+                                canvas.put(gx + px, gy + py, fg);
+                            }
+                        }
+                    }
+                }
+                None if gw > 2 && gh > 2 => {
+                    for px in 1..gw - 1 {
+                        canvas.put(gx + px, gy + 1, fg);
+                        canvas.put(gx + px, gy + gh - 2, fg);
+                    }
+                    for py in 1..gh - 1 {
+                        canvas.put(gx + 1, gy + py, fg);
+                        canvas.put(gx + gw - 2, gy + py, fg);
+                    }
+                }
+                None => {}
+            }
+        }
+    }
+    canvas
+}
@@ -0,0 +1,253 @@
+use std::io::{self, BufRead, BufReader, Read};
+use std::vec;
+
+use crate::art::{next_block, Art, ExtraBlock};
+use crate::content::{Frame, Frames};
+use crate::error::{Error, Result};
+use crate::header::Header;
+use crate::lines::{Fix, Lines, ParseOptions};
+
+#[derive(Debug)]
+enum State {
+    Body,
+    Legacy(vec::IntoIter<Frame>),
+    Done,
+}
+
+/// Streaming reader of 3a art.
+///
+/// The header and every block before the body are read on creation, then
+/// body frames are parsed one at a time, with pins applied to each of them
+/// as it is yielded. Blocks after the body are read once the frames run out.
+/// Pin blocks must precede the body, as frames already yielded can not be
+/// changed; a pin after the body is reported as [`Error::PinAfterBody`].
+///
+/// Legacy files have no blocks and are read whole on creation.
+#[derive(Debug)]
+pub struct ArtReader<I> {
+    lines: Lines<I>,
+    header: Header,
+    pins: Frames,
+    attached: Option<String>,
+    extra: Vec<ExtraBlock>,
+    index: usize,
+    body_read: bool,
+    state: State,
+}
+
+impl<R: Read> ArtReader<io::Lines<BufReader<R>>> {
+    /// Reads the header of an art from any reader.
+    pub fn new(r: R) -> Result<Self> {
+        Self::from_lines(Lines::from_reader(r))
+    }
+
+    /// Reads the header of an art from any reader with the given options.
+    pub fn with_options(r: R, options: &ParseOptions) -> Result<Self> {
+        Self::from_lines(Lines::with_options(
+            BufReader::new(r).lines(),
+            options.clone(),
+        ))
+    }
+}
+
+impl<I: Iterator<Item = io::Result<String>>> ArtReader<I> {
+    /// Reads the header of an art from counted lines.
+    pub fn from_lines(mut lines: Lines<I>) -> Result<Self> {
+        let header = Header::read_lines(&mut lines)?;
+        let mut reader = Self {
+            lines,
+            header,
+            pins: Frames::new(0, 0, 0, Default::default()),
+            attached: None,
+            extra: Vec::new(),
+            index: 0,
+            body_read: false,
+            state: State::Done,
+        };
+        if let Some(legacy) = reader.header.legacy {
+            let frames = Frames::read_legacy(legacy, &mut reader.lines, &mut reader.header)?;
+            reader.pins.width = frames.width;
+            reader.pins.height = frames.height;
+            reader.state = State::Legacy(frames.frames.into_iter());
+        } else {
+            reader.read_blocks()?;
+        }
+        Ok(reader)
+    }
+
+    /// Returns the art header.
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Returns the frame width, 0 until a frame or pin is read.
+    pub fn width(&self) -> usize {
+        self.pins.width
+    }
+
+    /// Returns the frame height, 0 until a frame or pin is read.
+    pub fn height(&self) -> usize {
+        self.pins.height
+    }
+
+    /// Returns the attached content read so far.
+    pub fn attached(&self) -> Option<&str> {
+        self.attached.as_deref()
+    }
+
+    /// Returns the extra blocks read so far.
+    pub fn extra(&self) -> &[ExtraBlock] {
+        &self.extra
+    }
+
+    /// Returns the fixes applied so far in lenient mode.
+    pub fn fixes(&self) -> &[Fix] {
+        self.lines.fixes()
+    }
+
+    /// Reads the next frame with pins applied.
+    /// Returns `None` once the whole input is read.
+    pub fn next_frame(&mut self) -> Result<Option<Frame>> {
+        let result = self.advance();
+        if result.is_err() {
+            self.state = State::Done;
+        }
+        result
+    }
+
+    /// Reads all remaining frames into an Art.
+    pub fn into_art(mut self) -> Result<Art> {
+        let mut frames = Vec::new();
+        while let Some(frame) = self.next_frame()? {
+            frames.push(frame);
+        }
+        let frames = Frames {
+            text_pin: None,
+            color_pin: None,
+            width: self.pins.width,
+            height: self.pins.height,
+            frames,
+        };
+        Art::from_components(self.header, frames, self.attached, self.extra)
+    }
+
+    fn advance(&mut self) -> Result<Option<Frame>> {
+        loop {
+            match &mut self.state {
+                State::Done => return Ok(None),
+                State::Legacy(frames) => {
+                    let frame = frames.next();
+                    if frame.is_none() {
+                        self.state = State::Done;
+                    }
+                    return Ok(frame);
+                }
+                State::Body => {
+                    let frame =
+                        self.pins
+                            .read_body_frame(&mut self.lines, &self.header, self.index)?;
+                    match frame {
+                        Some(frame) => {
+                            let frame = self
+                                .pins
+                                .pin_frame(&frame)
+                                .map_err(|err| self.lines.locate_block(err, "body", None))?;
+                            self.index += 1;
+                            return Ok(Some(frame));
+                        }
+                        None => {
+                            self.state = State::Done;
+                            self.read_blocks()?;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Reads blocks up to the next body.
+    fn read_blocks(&mut self) -> Result<()> {
+        let lines = &mut self.lines;
+        while let Some(title) = next_block(lines)? {
+            match title.as_str() {
+                "attach" => {
+                    if let Some(line) = lines.next() {
+                        self.attached = Some(line?);
+                    }
+                }
+                "text-pin" | "color-pin" if self.body_read => {
+                    let err = Error::PinAfterBody(title.clone());
+                    return Err(lines.locate_block(err, &title, None));
+                }
+                "text-pin" => self.pins.read_text_pin(lines)?,
+                "color-pin" => self.pins.read_color_pin(lines)?,
+                "body" => {
+                    self.body_read = true;
+                    self.state = State::Body;
+                    return Ok(());
+                }
+                title => {
+                    let block = ExtraBlock::read(title, lines)
+                        .map_err(|err| lines.locate_block(err, title, None))?;
+                    self.extra.push(block);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<I: Iterator<Item = io::Result<String>>> Iterator for ArtReader<I> {
+    type Item = Result<Frame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_frame().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn streams_same_frames() {
+        let src = include_str!("../examples/dna.3a");
+        let art = Art::from_str(src).unwrap();
+        let mut reader = ArtReader::new(src.as_bytes()).unwrap();
+        assert_eq!(reader.header().title.as_deref(), Some("DNA"));
+        let mut count = 0;
+        while let Some(frame) = reader.next_frame().unwrap() {
+            assert_eq!(Some(frame), art.frame(count));
+            count += 1;
+        }
+        assert_eq!(count, art.frames());
+        assert_eq!(
+            (reader.width(), reader.height()),
+            (art.width(), art.height())
+        );
+    }
+
+    #[test]
+    fn merges_pins_lazily() {
+        let src = "@3a\ncol x fg:red\n\n@color-pin\nxx\n\n@body\nab\n\ncd\n\n\n@notes\nhi\n";
+        let frames: Vec<Frame> = ArtReader::new(src.as_bytes())
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(frames.len(), 2);
+        assert!(frames.iter().all(|frame| frame.rows[0][1].color.is_some()));
+
+        let mut reader = ArtReader::new(src.as_bytes()).unwrap();
+        assert!(reader.extra().is_empty());
+        assert_eq!(reader.by_ref().count(), 2);
+        assert_eq!(reader.extra()[0].title, "notes");
+
+        let src = "@3a\n\n@body\nab\n\n\n@text-pin\ncd\n";
+        let err = ArtReader::new(src.as_bytes())
+            .unwrap()
+            .find_map(|frame| frame.err())
+            .unwrap();
+        assert!(matches!(err.inner(), Error::PinAfterBody(_)));
+    }
+}
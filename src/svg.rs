@@ -0,0 +1,176 @@
+use crate::chars::Char;
+use crate::colors::{CSSColorMap, Color, Palette};
+use crate::content::Frame;
+use crate::timeline::Timeline;
+
+/// How animated SVG switches between frames.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SvgAnimation {
+    /// SMIL `<animate>` elements on frame opacity.
+    #[default]
+    Smil,
+    /// CSS `@keyframes` with `steps()` timing on frame opacity,
+    /// for renderers without SMIL support.
+    Css,
+}
+
+/// Options for exporting animated SVG.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SvgOptions {
+    /// Emits runs of identical consecutive frames once, shown for the sum
+    /// of their delays.
+    pub skip_unchanged: bool,
+    /// Animation mechanism.
+    pub animation: SvgAnimation,
+    /// Number of times the animation is played before it stops on the last
+    /// frame, `None` to play forever.
+    pub plays: Option<usize>,
+}
+
+/// Returns the CSS class of a palette entry: `f` or `b` (foreground or
+/// background) followed by the hex code point of the entry name.
+pub(crate) fn class_name(name: Char, foreground: bool) -> String {
+    format!(
+        "{}{:x}",
+        if foreground { 'f' } else { 'b' },
+        name.char as u32
+    )
+}
+
+/// Generates a style block with the default text fill and fill classes
+/// for every palette entry used in `frames`.
+pub(crate) fn color_style<'a>(
+    frames: impl IntoIterator<Item = &'a Frame>,
+    palette: &Palette,
+    map: &CSSColorMap,
+) -> String {
+    let mut names: Vec<Char> = Vec::new();
+    for frame in frames {
+        for name in frame.rows.iter().flatten().filter_map(|c| c.color) {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    let mut style = format!(
+        "<style>\ntext {{ fill: {}; }}\n",
+        map.map(Color::None, true)
+    );
+    for name in names {
+        let pair = palette.get_color(name);
+        if pair.fg != Color::None {
+            style += &format!(
+                ".{} {{ fill: {}; }}\n",
+                class_name(name, true),
+                map.map(pair.fg, true)
+            );
+        }
+        if pair.bg != Color::None {
+            style += &format!(
+                ".{} {{ fill: {}; }}\n",
+                class_name(name, false),
+                map.map(pair.bg, false)
+            );
+        }
+    }
+    style += "</style>\n";
+    style
+}
+
+/// Merges runs of consecutive equal frames.
+/// Returns the index of the first frame of every run with the run delay.
+pub(crate) fn unchanged_runs(frames: &[Frame], delays: &[usize]) -> Vec<(usize, usize)> {
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    for (f, frame) in frames.iter().enumerate() {
+        let delay = delays.get(f).copied().unwrap_or(0);
+        match runs.last_mut() {
+            Some((first, total)) if frames[*first] == *frame => *total += delay,
+            _ => runs.push((f, delay)),
+        }
+    }
+    runs
+}
+
+/// Generates a style block animating frame groups of class `a3-f{i}`
+/// with CSS keyframes. Each frame is shown from its start to its end time
+/// with `steps(1, end)` holding the opacity between keyframes.
+/// After a finite number of `plays` the last frame stays visible.
+pub(crate) fn css_keyframes(timeline: &Timeline, plays: Option<usize>) -> String {
+    let total = timeline.duration_ms();
+    let percent = |ms: usize| {
+        let mut s = format!("{:.4}", ms as f64 * 100.0 / total.max(1) as f64);
+        while s.ends_with('0') {
+            s.pop();
+        }
+        if s.ends_with('.') {
+            s.pop();
+        }
+        s + "%"
+    };
+    let mut style = String::from("<style>\n");
+    for (f, times) in timeline.timestamps_ms().windows(2).enumerate() {
+        let (start, end) = (times[0], times[1]);
+        let mut frames = Vec::new();
+        if start > 0 {
+            frames.push("0% { opacity: 0; }".to_string());
+        }
+        if end > start {
+            frames.push(format!("{} {{ opacity: 1; }}", percent(start)));
+        }
+        if end < total || end == start {
+            frames.push(format!("{} {{ opacity: 0; }}", percent(end)));
+        } else if plays.is_some() {
+            frames.push("100% { opacity: 1; }".to_string());
+        }
+        style += &format!("@keyframes a3-f{} {{ {} }}\n", f, frames.join(" "));
+        style += &format!(
+            ".a3-f{} {{ animation: a3-f{} {}s steps(1, end) {}; }}\n",
+            f,
+            f,
+            total as f64 / 1000.0,
+            match plays {
+                Some(plays) => format!("{} forwards", plays),
+                None => "infinite".to_string(),
+            }
+        );
+    }
+    style += "</style>\n";
+    style
+}
+
+/// Formats the `keyTimes` of discrete SMIL frame animations: the start of
+/// every frame and the end of the last one as fractions of the duration.
+pub(crate) fn smil_key_times(timeline: &Timeline) -> String {
+    let total = timeline.duration_ms();
+    let key_times: Vec<String> = timeline
+        .timestamps_ms()
+        .iter()
+        .map(|ms| {
+            let s = format!("{:.6}", *ms as f64 / total as f64);
+            if s.contains('.') {
+                s.trim_end_matches('0').trim_end_matches('.').into()
+            } else {
+                s
+            }
+        })
+        .collect();
+    key_times.join(";")
+}
+
+/// Formats the opacity `values` of the SMIL animation of one of `frames`
+/// frames, matching [`smil_key_times`].
+/// With `hold_last` the last frame keeps its opacity at the end, so it
+/// stays visible once a frozen animation stops.
+pub(crate) fn smil_values(frame: usize, frames: usize, hold_last: bool) -> String {
+    let values: Vec<&str> = (0..=frames)
+        .map(|f| {
+            let held = hold_last && f == frames && frame + 1 == frames;
+            if f == frame || held {
+                "1"
+            } else {
+                "0"
+            }
+        })
+        .collect();
+    values.join(";")
+}
@@ -0,0 +1,570 @@
+use crate::art::Art;
+use crate::chars::{Char, SPACE};
+use crate::colors::apply_sgr;
+use crate::content::{Cell, Frame};
+use crate::{Color, ColorPair};
+
+/// Default terminal width used when a recording carries no size information.
+pub const DEFAULT_WIDTH: usize = 80;
+/// Default terminal height used when a recording carries no size information.
+pub const DEFAULT_HEIGHT: usize = 24;
+
+/// Longest CSI parameter string kept; further parameter chars are dropped.
+const MAX_CSI_PARAMS: usize = 64;
+
+/// A minimal VT100/xterm screen model.
+///
+/// The terminal keeps a persistent grid of cells, so output can be fed to it
+/// in arbitrary chunks (e.g. ttyrec or asciicast payloads) and the visible
+/// screen can be captured as a [`Frame`] at any point.
+/// Supported are printable text with auto-wrap and scrolling, the common C0
+/// controls, cursor movement (CUU/CUD/CUF/CUB/CNL/CPL/CHA/CUP/VPA),
+/// erase in line/display, insert/delete of chars and lines, scroll regions,
+/// cursor save/restore and SGR colors. Unknown sequences are skipped.
+#[derive(Debug, Clone)]
+pub struct Terminal {
+    width: usize,
+    height: usize,
+    grid: Vec<Vec<(Char, ColorPair)>>,
+    col: usize,
+    row: usize,
+    wrap_pending: bool,
+    pen: ColorPair,
+    saved: (usize, usize, ColorPair),
+    scroll_top: usize,
+    scroll_bottom: usize,
+    newline_mode: bool,
+    used_width: usize,
+    used_height: usize,
+    title: Option<String>,
+    state: ParseState,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ParseState {
+    Ground,
+    Escape,
+    EscapeSkip,
+    Csi(String),
+    Osc(String),
+    OscEscape(String),
+}
+
+impl Default for Terminal {
+    /// Creates an 80x24 terminal.
+    fn default() -> Self {
+        Self::new(DEFAULT_WIDTH, DEFAULT_HEIGHT)
+    }
+}
+
+impl Terminal {
+    /// Creates a blank terminal with the given screen size.
+    /// Zero dimensions are bumped to 1.
+    pub fn new(width: usize, height: usize) -> Self {
+        let width = width.max(1);
+        let height = height.max(1);
+        Self {
+            width,
+            height,
+            grid: vec![vec![(SPACE, ColorPair::default()); width]; height],
+            col: 0,
+            row: 0,
+            wrap_pending: false,
+            pen: ColorPair::default(),
+            saved: (0, 0, ColorPair::default()),
+            scroll_top: 0,
+            scroll_bottom: height - 1,
+            newline_mode: true,
+            used_width: 0,
+            used_height: 0,
+            title: None,
+            state: ParseState::Ground,
+        }
+    }
+
+    /// Sets whether a line feed also returns the cursor to the first column.
+    /// Enabled by default, matching what a tty with `onlcr` produces.
+    pub fn set_newline_mode(&mut self, enabled: bool) {
+        self.newline_mode = enabled;
+    }
+
+    /// Returns the screen width in columns.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Returns the screen height in rows.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns the cursor position as (column, row).
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.col, self.row)
+    }
+
+    /// Returns the size of the screen area that has been drawn on so far,
+    /// as (columns, rows).
+    pub fn used_size(&self) -> (usize, usize) {
+        (self.used_width, self.used_height)
+    }
+
+    /// Returns the last window title set with an OSC 0 or OSC 2 sequence.
+    pub fn title(&self) -> Option<String> {
+        self.title.clone()
+    }
+
+    /// Returns the character and colors at (column, row).
+    pub fn get(&self, column: usize, row: usize) -> Option<(Char, ColorPair)> {
+        self.grid.get(row).and_then(|r| r.get(column)).copied()
+    }
+
+    /// Feeds terminal output to the screen model.
+    /// Escape sequences may be split between calls.
+    pub fn feed(&mut self, data: &str) {
+        for ch in data.chars() {
+            self.feed_char(ch);
+        }
+    }
+
+    /// Captures the visible screen as a frame.
+    /// Colors are resolved through the art palette, creating new
+    /// mappings as needed.
+    pub fn snapshot(&self, art: &mut Art) -> Frame {
+        let mut frame = Frame::new(self.width, self.height, Cell::default());
+        for (r, row) in self.grid.iter().enumerate() {
+            for (c, (text, pair)) in row.iter().enumerate() {
+                let color = if *pair != ColorPair::default() {
+                    Some(art.search_or_create_color_map(*pair))
+                } else {
+                    None
+                };
+                frame.set(c, r, Cell { text: *text, color });
+            }
+        }
+        frame
+    }
+
+    /// Clears the screen and resets cursor, colors and modes.
+    pub fn reset(&mut self) {
+        let mut term = Self::new(self.width, self.height);
+        term.newline_mode = self.newline_mode;
+        term.used_width = self.used_width;
+        term.used_height = self.used_height;
+        term.title = self.title.clone();
+        *self = term;
+    }
+}
+
+// Parser
+impl Terminal {
+    fn feed_char(&mut self, ch: char) {
+        let state = std::mem::replace(&mut self.state, ParseState::Ground);
+        self.state = match state {
+            ParseState::Ground => {
+                if ch == '\x1b' {
+                    ParseState::Escape
+                } else {
+                    self.ground(ch);
+                    ParseState::Ground
+                }
+            }
+            ParseState::Escape => self.escape(ch),
+            // Skips the single designator char of sequences like `ESC ( B`.
+            ParseState::EscapeSkip => ParseState::Ground,
+            ParseState::Csi(mut params) => {
+                if ('\x40'..='\x7e').contains(&ch) {
+                    self.csi(&params, ch);
+                    ParseState::Ground
+                } else if ch == '\x1b' {
+                    ParseState::Escape
+                } else {
+                    if params.len() < MAX_CSI_PARAMS {
+                        params.push(ch);
+                    }
+                    ParseState::Csi(params)
+                }
+            }
+            ParseState::Osc(mut text) => match ch {
+                '\x07' => {
+                    self.osc(&text);
+                    ParseState::Ground
+                }
+                '\x1b' => ParseState::OscEscape(text),
+                _ => {
+                    text.push(ch);
+                    ParseState::Osc(text)
+                }
+            },
+            ParseState::OscEscape(mut text) => {
+                if ch == '\\' {
+                    self.osc(&text);
+                    ParseState::Ground
+                } else {
+                    text.push(ch);
+                    ParseState::Osc(text)
+                }
+            }
+        }
+    }
+
+    fn ground(&mut self, ch: char) {
+        match ch {
+            '\r' => self.carriage_return(),
+            '\n' | '\x0b' | '\x0c' => {
+                self.line_feed();
+                if self.newline_mode {
+                    self.carriage_return();
+                }
+            }
+            '\x08' => {
+                self.wrap_pending = false;
+                self.col = self.col.saturating_sub(1);
+            }
+            '\t' => {
+                self.wrap_pending = false;
+                self.col = ((self.col / 8 + 1) * 8).min(self.width - 1);
+            }
+            _ => {
+                if let Ok(ch) = Char::new(ch) {
+                    self.print(ch);
+                }
+            }
+        }
+    }
+
+    fn escape(&mut self, ch: char) -> ParseState {
+        match ch {
+            '[' => return ParseState::Csi(String::new()),
+            ']' => return ParseState::Osc(String::new()),
+            '(' | ')' | '*' | '+' | '#' | '%' => return ParseState::EscapeSkip,
+            '7' => self.save_cursor(),
+            '8' => self.restore_cursor(),
+            'D' => self.line_feed(),
+            'E' => {
+                self.line_feed();
+                self.carriage_return();
+            }
+            'M' => self.reverse_index(),
+            'c' => self.reset(),
+            _ => {}
+        }
+        ParseState::Ground
+    }
+
+    fn osc(&mut self, text: &str) {
+        if let Some((code, value)) = text.split_once(';') {
+            if code == "0" || code == "2" {
+                self.title = Some(value.into());
+            }
+        }
+    }
+
+    fn csi(&mut self, params: &str, cmd: char) {
+        // Private sequences (`CSI ? ...`, `CSI > ...`) change modes
+        // that do not affect the screen content.
+        if params.starts_with(|c: char| ('\x3c'..='\x3f').contains(&c)) {
+            return;
+        }
+        let nums: Vec<Option<usize>> = params.split(';').map(|s| s.parse::<usize>().ok()).collect();
+        let arg = |i: usize, default: usize| -> usize {
+            match nums.get(i).copied().flatten() {
+                Some(0) | None => default,
+                Some(n) => n,
+            }
+        };
+        let raw = |i: usize| -> usize { nums.get(i).copied().flatten().unwrap_or(0) };
+        if cmd != 'm' {
+            self.wrap_pending = false;
+        }
+        match cmd {
+            'A' => self.row = self.row.saturating_sub(arg(0, 1)).max(self.top_limit()),
+            'B' | 'e' => self.row = self.row.saturating_add(arg(0, 1)).min(self.bottom_limit()),
+            'C' | 'a' => self.col = self.col.saturating_add(arg(0, 1)).min(self.width - 1),
+            'D' => self.col = self.col.saturating_sub(arg(0, 1)),
+            'E' => {
+                self.row = self.row.saturating_add(arg(0, 1)).min(self.bottom_limit());
+                self.col = 0;
+            }
+            'F' => {
+                self.row = self.row.saturating_sub(arg(0, 1)).max(self.top_limit());
+                self.col = 0;
+            }
+            'G' | '`' => self.col = (arg(0, 1) - 1).min(self.width - 1),
+            'd' => self.row = (arg(0, 1) - 1).min(self.height - 1),
+            'H' | 'f' => {
+                self.row = (arg(0, 1) - 1).min(self.height - 1);
+                self.col = (arg(1, 1) - 1).min(self.width - 1);
+            }
+            'J' => self.erase_display(raw(0)),
+            'K' => self.erase_line(raw(0)),
+            'X' => {
+                let end = self.col.saturating_add(arg(0, 1)).min(self.width);
+                self.erase_cells(self.row, self.col, end);
+            }
+            'P' => {
+                let n = arg(0, 1).min(self.width - self.col);
+                let pen = self.blank();
+                let row = &mut self.grid[self.row];
+                row.drain(self.col..self.col + n);
+                row.resize(self.width, pen);
+            }
+            '@' => {
+                let n = arg(0, 1).min(self.width - self.col);
+                let pen = self.blank();
+                let row = &mut self.grid[self.row];
+                for _ in 0..n {
+                    row.insert(self.col, pen);
+                }
+                row.truncate(self.width);
+            }
+            'L' if self.row >= self.scroll_top && self.row <= self.scroll_bottom => {
+                self.scroll_down_region(self.row, self.scroll_bottom, arg(0, 1));
+            }
+            'M' if self.row >= self.scroll_top && self.row <= self.scroll_bottom => {
+                self.scroll_up_region(self.row, self.scroll_bottom, arg(0, 1));
+            }
+            'S' => self.scroll_up_region(self.scroll_top, self.scroll_bottom, arg(0, 1)),
+            'T' => self.scroll_down_region(self.scroll_top, self.scroll_bottom, arg(0, 1)),
+            'r' => {
+                let top = arg(0, 1) - 1;
+                let bottom = arg(1, self.height).min(self.height) - 1;
+                if top < bottom {
+                    self.scroll_top = top;
+                    self.scroll_bottom = bottom;
+                    self.row = 0;
+                    self.col = 0;
+                }
+            }
+            's' => self.save_cursor(),
+            'u' => self.restore_cursor(),
+            'm' => {
+                let sgr: Vec<i32> = if params.is_empty() {
+                    vec![]
+                } else {
+                    params
+                        .split(';')
+                        .map(|s| {
+                            if s.is_empty() {
+                                0
+                            } else {
+                                s.parse::<i32>().unwrap_or(-999)
+                            }
+                        })
+                        .collect()
+                };
+                apply_sgr(&sgr, &mut self.pen.fg, &mut self.pen.bg);
+            }
+            _ => {}
+        }
+    }
+}
+
+// Screen operations
+impl Terminal {
+    fn blank(&self) -> (Char, ColorPair) {
+        (
+            SPACE,
+            ColorPair {
+                fg: Color::None,
+                bg: self.pen.bg,
+            },
+        )
+    }
+
+    fn top_limit(&self) -> usize {
+        if self.row >= self.scroll_top {
+            self.scroll_top
+        } else {
+            0
+        }
+    }
+
+    fn bottom_limit(&self) -> usize {
+        if self.row <= self.scroll_bottom {
+            self.scroll_bottom
+        } else {
+            self.height - 1
+        }
+    }
+
+    fn print(&mut self, ch: Char) {
+        if self.wrap_pending {
+            self.wrap_pending = false;
+            self.line_feed();
+            self.carriage_return();
+        }
+        self.grid[self.row][self.col] = (ch, self.pen);
+        self.used_width = self.used_width.max(self.col + 1);
+        self.used_height = self.used_height.max(self.row + 1);
+        if self.col + 1 < self.width {
+            self.col += 1;
+        } else {
+            self.wrap_pending = true;
+        }
+    }
+
+    fn carriage_return(&mut self) {
+        self.wrap_pending = false;
+        self.col = 0;
+    }
+
+    fn line_feed(&mut self) {
+        self.wrap_pending = false;
+        if self.row == self.scroll_bottom {
+            self.scroll_up_region(self.scroll_top, self.scroll_bottom, 1);
+        } else if self.row + 1 < self.height {
+            self.row += 1;
+        }
+    }
+
+    fn reverse_index(&mut self) {
+        self.wrap_pending = false;
+        if self.row == self.scroll_top {
+            self.scroll_down_region(self.scroll_top, self.scroll_bottom, 1);
+        } else if self.row > 0 {
+            self.row -= 1;
+        }
+    }
+
+    fn scroll_up_region(&mut self, top: usize, bottom: usize, n: usize) {
+        let n = n.min(bottom + 1 - top);
+        let blank = vec![self.blank(); self.width];
+        self.grid[top..=bottom].rotate_left(n);
+        for row in &mut self.grid[bottom + 1 - n..=bottom] {
+            *row = blank.clone();
+        }
+    }
+
+    fn scroll_down_region(&mut self, top: usize, bottom: usize, n: usize) {
+        let n = n.min(bottom + 1 - top);
+        let blank = vec![self.blank(); self.width];
+        self.grid[top..=bottom].rotate_right(n);
+        for row in &mut self.grid[top..top + n] {
+            *row = blank.clone();
+        }
+    }
+
+    fn erase_cells(&mut self, row: usize, from: usize, to: usize) {
+        let blank = self.blank();
+        for cell in &mut self.grid[row][from..to] {
+            *cell = blank;
+        }
+    }
+
+    fn erase_line(&mut self, mode: usize) {
+        match mode {
+            0 => self.erase_cells(self.row, self.col, self.width),
+            1 => self.erase_cells(self.row, 0, self.col + 1),
+            2 => self.erase_cells(self.row, 0, self.width),
+            _ => {}
+        }
+    }
+
+    fn erase_display(&mut self, mode: usize) {
+        match mode {
+            0 => {
+                self.erase_cells(self.row, self.col, self.width);
+                for r in self.row + 1..self.height {
+                    self.erase_cells(r, 0, self.width);
+                }
+            }
+            1 => {
+                for r in 0..self.row {
+                    self.erase_cells(r, 0, self.width);
+                }
+                self.erase_cells(self.row, 0, self.col + 1);
+            }
+            2 | 3 => {
+                for r in 0..self.height {
+                    self.erase_cells(r, 0, self.width);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn save_cursor(&mut self) {
+        self.saved = (self.col, self.row, self.pen);
+    }
+
+    fn restore_cursor(&mut self) {
+        let (col, row, pen) = self.saved;
+        self.col = col.min(self.width - 1);
+        self.row = row.min(self.height - 1);
+        self.pen = pen;
+        self.wrap_pending = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Color4;
+
+    fn row_text(term: &Terminal, row: usize) -> String {
+        (0..term.width())
+            .map(|c| term.get(c, row).unwrap().0.char)
+            .collect()
+    }
+
+    #[test]
+    fn print_and_wrap() {
+        let mut term = Terminal::new(4, 2);
+        term.feed("abcdef");
+        assert_eq!(row_text(&term, 0), "abcd");
+        assert_eq!(row_text(&term, 1), "ef  ");
+        assert_eq!(term.cursor(), (2, 1));
+    }
+
+    #[test]
+    fn scrolls_at_bottom() {
+        let mut term = Terminal::new(3, 2);
+        term.feed("a\nb\nc");
+        assert_eq!(row_text(&term, 0), "b  ");
+        assert_eq!(row_text(&term, 1), "c  ");
+    }
+
+    #[test]
+    fn huge_and_long_parameters() {
+        let mut term = Terminal::new(3, 2);
+        term.feed("\x1b[18446744073709551615C\x1b[18446744073709551615Ea");
+        term.feed("\x1b[18446744073709551615X\x1b[18446744073709551615B");
+        assert_eq!(term.cursor(), (1, 1));
+        term.feed(&format!("\x1b[{}Hb", "1;".repeat(1000)));
+        assert_eq!(row_text(&term, 0), "b  ");
+    }
+
+    #[test]
+    fn cursor_movement_and_erase() {
+        let mut term = Terminal::new(5, 3);
+        term.feed("xxxxx\r\nyyyyy\r\nzzzzz");
+        term.feed("\x1b[2A\x1b[2G\x1b[K");
+        assert_eq!(row_text(&term, 0), "x    ");
+        term.feed("\x1b[3;3H\x1b[1K");
+        assert_eq!(row_text(&term, 2), "   zz");
+        term.feed("\x1b[2J");
+        assert_eq!(row_text(&term, 1), "     ");
+    }
+
+    #[test]
+    fn sequences_split_between_feeds() {
+        let mut term = Terminal::new(4, 1);
+        term.feed("\x1b[3");
+        term.feed("1mA\x1b]0;ti");
+        term.feed("tle\x07");
+        let (ch, pair) = term.get(0, 0).unwrap();
+        assert_eq!(ch.char, 'A');
+        assert_eq!(pair.fg, Color::Color4(Color4::Red, false));
+        assert_eq!(term.title(), Some("title".into()));
+    }
+
+    #[test]
+    fn insert_and_delete_chars() {
+        let mut term = Terminal::new(5, 1);
+        term.feed("abcde\x1b[1G\x1b[2P");
+        assert_eq!(row_text(&term, 0), "cde  ");
+        term.feed("\x1b[2@");
+        assert_eq!(row_text(&term, 0), "  cde");
+    }
+}
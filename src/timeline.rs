@@ -0,0 +1,150 @@
+use std::time::Duration;
+
+use crate::delay::Delay;
+
+/// Playback timing of an animation: when every frame starts and how long
+/// it is shown. Times are kept in whole milliseconds, like 3a delays.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Timeline {
+    /// Start of every frame followed by the end of the last one.
+    timestamps: Vec<usize>,
+}
+
+impl Timeline {
+    /// Creates a timeline from frame delays in milliseconds.
+    pub fn new(delays: &[usize]) -> Self {
+        let mut timestamps = Vec::with_capacity(delays.len() + 1);
+        let mut time = 0;
+        timestamps.push(time);
+        for delay in delays {
+            time += delay;
+            timestamps.push(time);
+        }
+        Self { timestamps }
+    }
+
+    /// Creates a timeline of `frames` frames from a delay map.
+    pub fn from_delay(delay: &Delay, frames: usize) -> Self {
+        Self::new(&delay.to_vec_delays(frames))
+    }
+
+    /// Returns the number of frames.
+    pub fn frames(&self) -> usize {
+        self.timestamps.len() - 1
+    }
+
+    /// Returns the total duration.
+    pub fn duration(&self) -> Duration {
+        Duration::from_millis(self.duration_ms() as u64)
+    }
+
+    /// Returns the time a frame starts at.
+    pub fn start_time(&self, frame: usize) -> Option<Duration> {
+        if frame < self.frames() {
+            Some(Duration::from_millis(self.timestamps[frame] as u64))
+        } else {
+            None
+        }
+    }
+
+    /// Returns how long a frame is shown.
+    pub fn frame_duration(&self, frame: usize) -> Option<Duration> {
+        if frame < self.frames() {
+            let delay = self.timestamps[frame + 1] - self.timestamps[frame];
+            Some(Duration::from_millis(delay as u64))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the start of every frame followed by the end of the last one.
+    pub fn timestamps(&self) -> Vec<Duration> {
+        self.timestamps
+            .iter()
+            .map(|ms| Duration::from_millis(*ms as u64))
+            .collect()
+    }
+
+    /// Returns the frame shown at `time`, or `None` from the end on.
+    /// Sub-millisecond parts of `time` are ignored.
+    pub fn frame_at(&self, time: Duration) -> Option<usize> {
+        let time = time.as_millis();
+        if time >= self.duration_ms() as u128 {
+            return None;
+        }
+        let starts = &self.timestamps[..self.frames()];
+        Some(starts.partition_point(|start| *start as u128 <= time) - 1)
+    }
+
+    /// Iterates over frames as (frame, start, duration).
+    pub fn iter(&self) -> impl Iterator<Item = (usize, Duration, Duration)> + '_ {
+        self.timestamps.windows(2).enumerate().map(|(f, w)| {
+            (
+                f,
+                Duration::from_millis(w[0] as u64),
+                Duration::from_millis((w[1] - w[0]) as u64),
+            )
+        })
+    }
+
+    /// Returns the start of every frame followed by the end of the last
+    /// one, in milliseconds.
+    pub(crate) fn timestamps_ms(&self) -> &[usize] {
+        &self.timestamps
+    }
+
+    pub(crate) fn duration_ms(&self) -> usize {
+        self.timestamps[self.frames()]
+    }
+}
+
+/// An empty timeline without frames.
+impl Default for Timeline {
+    fn default() -> Self {
+        Self::new(&[])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn lookup() {
+        let timeline = Timeline::new(&[100, 50, 250]);
+        assert_eq!(timeline.frames(), 3);
+        assert_eq!(timeline.duration(), ms(400));
+        assert_eq!(
+            timeline.timestamps(),
+            vec![ms(0), ms(100), ms(150), ms(400)]
+        );
+        assert_eq!(timeline.start_time(2), Some(ms(150)));
+        assert_eq!(timeline.start_time(3), None);
+        assert_eq!(timeline.frame_duration(1), Some(ms(50)));
+        assert_eq!(timeline.frame_at(ms(0)), Some(0));
+        assert_eq!(timeline.frame_at(ms(99)), Some(0));
+        assert_eq!(timeline.frame_at(ms(100)), Some(1));
+        assert_eq!(timeline.frame_at(Duration::from_micros(149_999)), Some(1));
+        assert_eq!(timeline.frame_at(ms(399)), Some(2));
+        assert_eq!(timeline.frame_at(ms(400)), None);
+        let frames: Vec<_> = timeline.iter().collect();
+        assert_eq!(frames[1], (1, ms(100), ms(50)));
+        assert_eq!(frames.len(), 3);
+    }
+
+    #[test]
+    fn empty_and_instant_frames() {
+        let timeline = Timeline::new(&[]);
+        assert_eq!(timeline, Timeline::default());
+        assert_eq!(timeline.frames(), 0);
+        assert_eq!(timeline.duration(), ms(0));
+        assert_eq!(timeline.frame_at(ms(0)), None);
+
+        let timeline = Timeline::new(&[10, 0, 10]);
+        assert_eq!(timeline.frame_at(ms(10)), Some(2));
+    }
+}
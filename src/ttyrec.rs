@@ -1,8 +1,38 @@
 use std::convert::TryInto;
 use std::io::Read;
 
+use crate::term::{DEFAULT_HEIGHT, DEFAULT_WIDTH};
 use crate::Error;
 
+/// Options for importing ttyrec recordings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TtyrecOptions {
+    /// Width of the emulated terminal in columns.
+    pub width: usize,
+    /// Height of the emulated terminal in rows.
+    pub height: usize,
+    /// Lower bound for frame delays in milliseconds (at least 1).
+    pub min_delay: usize,
+    /// Upper bound for frame delays in milliseconds, if any.
+    pub max_delay: Option<usize>,
+    /// Merges consecutive payloads that leave the screen unchanged into one
+    /// frame, summing their delays.
+    pub collapse_idle: bool,
+}
+
+impl Default for TtyrecOptions {
+    /// Emulates an 80x24 terminal and keeps the original timing.
+    fn default() -> Self {
+        Self {
+            width: DEFAULT_WIDTH,
+            height: DEFAULT_HEIGHT,
+            min_delay: 1,
+            max_delay: None,
+            collapse_idle: false,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TtyrecFrame {
     pub(crate) timestamp_ms: usize,
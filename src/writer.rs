@@ -0,0 +1,156 @@
+use std::io::Write;
+
+use crate::art::ExtraBlock;
+use crate::content::Frame;
+use crate::error::{Error, Result};
+use crate::header::Header;
+use crate::helpers::Fmt;
+
+/// Incremental writer of 3a art.
+///
+/// The header is written on creation and every frame is written to the
+/// body as soon as it is given, so memory use does not grow with the number
+/// of frames.
+///
+/// Frames are not kept, so channels shared by all frames can not be
+/// detected: the writer never emits `@text-pin` or `@color-pin` blocks and
+/// writes every frame whole, with its color channel when the header enables
+/// colors. Output is therefore larger than the `Display` output of the same
+/// [`Art`](crate::Art) when a channel could be pinned.
+#[derive(Debug)]
+pub struct ArtWriter<W: Write> {
+    inner: W,
+    colors: bool,
+    width: usize,
+    height: usize,
+    frames: usize,
+    body: bool,
+}
+
+impl<W: Write> ArtWriter<W> {
+    /// Writes the header and returns a writer for the rest of the art.
+    /// Frames are written with colors if the header enables them, either
+    /// with the `colors` key or with palette entries.
+    pub fn new(mut inner: W, header: &Header) -> Result<Self> {
+        let colors = header.get_colors();
+        write!(inner, "{}", Fmt(|f| header.fmt_with_colors(f, colors)))?;
+        Ok(Self {
+            inner,
+            colors,
+            width: 0,
+            height: 0,
+            frames: 0,
+            body: false,
+        })
+    }
+
+    /// Returns the number of frames written.
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    /// Writes a frame to the body.
+    /// All frames must have the size of the first one.
+    pub fn write_frame(&mut self, frame: &Frame) -> Result<()> {
+        if self.frames > 0 {
+            if frame.width() != self.width {
+                return Err(Error::WidthMismatch);
+            }
+            if frame.height() != self.height {
+                return Err(Error::HeightMismatch);
+            }
+        } else {
+            self.width = frame.width();
+            self.height = frame.height();
+        }
+        if !self.body {
+            writeln!(self.inner, "@body")?;
+            self.body = true;
+        }
+        if self.colors {
+            write!(self.inner, "{}", Fmt(|f| frame.fmt_both(f)))?;
+        } else {
+            write!(self.inner, "{}", Fmt(|f| frame.fmt_text(f)))?;
+        }
+        writeln!(self.inner)?;
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Writes an extra block, ending the body if one is open.
+    /// Frames written afterwards start a new body.
+    pub fn write_block(&mut self, block: &ExtraBlock) -> Result<()> {
+        self.end_body()?;
+        write!(self.inner, "{}", block)?;
+        Ok(())
+    }
+
+    /// Writes an attach block, ending the body if one is open.
+    pub fn write_attached(&mut self, attached: &str) -> Result<()> {
+        self.end_body()?;
+        writeln!(self.inner, "@attach\n{}\n", attached)?;
+        Ok(())
+    }
+
+    /// Ends the art and flushes the output, returning the inner writer.
+    /// An empty body is written if no frames were.
+    pub fn finish(mut self) -> Result<W> {
+        if self.frames == 0 {
+            writeln!(self.inner, "@body")?;
+        }
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    fn end_body(&mut self) -> Result<()> {
+        if self.body {
+            writeln!(self.inner)?;
+            self.body = false;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Art;
+    use std::str::FromStr;
+
+    #[test]
+    fn writes_readable_art() {
+        let art = Art::from_str(include_str!("../examples/dna.3a")).unwrap();
+        let (header, frames, _, _) = art.clone().to_components();
+        let mut writer = ArtWriter::new(Vec::new(), &header).unwrap();
+        for frame in &frames.frames {
+            writer.write_frame(frame).unwrap();
+        }
+        let block = ExtraBlock {
+            title: "notes".into(),
+            content: "streamed\n".into(),
+        };
+        writer.write_block(&block).unwrap();
+        assert_eq!(writer.frames(), art.frames());
+        let out = String::from_utf8(writer.finish().unwrap()).unwrap();
+        assert!(!out.contains("-pin"));
+
+        let read = Art::from_str(&out).unwrap();
+        assert_eq!(read.frames(), art.frames());
+        for i in 0..art.frames() {
+            assert_eq!(read.frame(i), art.frame(i));
+        }
+        assert_eq!(read.extra[0].content, "streamed\n");
+    }
+
+    #[test]
+    fn rejects_frame_size_changes() {
+        let mut writer = ArtWriter::new(Vec::new(), &Header::default()).unwrap();
+        writer
+            .write_frame(&Frame::new(2, 2, Default::default()))
+            .unwrap();
+        let err = writer.write_frame(&Frame::new(3, 2, Default::default()));
+        assert!(matches!(err, Err(Error::WidthMismatch)));
+        let out = writer.finish().unwrap();
+        assert_eq!(out, b"@3a\n\n@body\n  \n  \n\n");
+    }
+}